@@ -0,0 +1,422 @@
+use crate::buffer::token::TokenIterator;
+use crate::buffer::{Buffer, LineRange, Position, ScopeStack, Token, TokenSet};
+use crate::errors::*;
+use syntect::parsing::SyntaxSet;
+use unicode_segmentation::UnicodeSegmentation;
+
+pub(crate) const BRACKETS: [(&str, &str); 3] = [("(", ")"), ("[", "]"), ("{", "}")];
+const QUOTES: [&str; 3] = ["\"", "'", "`"];
+
+// A bracket or quote grapheme, along with its scope.
+pub(crate) struct Delimiter {
+    pub(crate) value: String,
+    pub(crate) position: Position,
+    pub(crate) scope: ScopeStack,
+}
+
+impl Delimiter {
+    pub(crate) fn is_code(&self) -> bool {
+        !self.scope.as_slice().iter().any(|scope| {
+            let scope = scope.build_string();
+            has_prefix(&scope, "string") || has_prefix(&scope, "comment")
+        })
+    }
+
+    pub(crate) fn is_string_punctuation(&self, kind: &str) -> bool {
+        self.scope.as_slice().last().is_some_and(|scope| {
+            has_prefix(
+                &scope.build_string(),
+                &format!("punctuation.definition.string.{}", kind),
+            )
+        })
+    }
+}
+
+// Tracks what a delimiter is matched by, while scanning away from it.
+struct Matcher {
+    // Whether the match follows the delimiter, rather than preceding it.
+    forward: bool,
+    target: Target,
+}
+
+enum Target {
+    // The closing (or opening) bracket, skipping over nested pairs.
+    Bracket {
+        nested: &'static str,
+        value: &'static str,
+        depth: usize,
+    },
+    // The other end of a string, at the same scope depth.
+    Quote {
+        punctuation: &'static str,
+        depth: usize,
+    },
+}
+
+impl Matcher {
+    // Returns None if the delimiter can't be matched (e.g. a bracket
+    // inside of a string, or a quote that doesn't delimit one).
+    fn new(delimiter: &Delimiter) -> Option<Matcher> {
+        if let Some(&(open, close)) = BRACKETS
+            .iter()
+            .find(|&&(open, close)| delimiter.value == open || delimiter.value == close)
+        {
+            if !delimiter.is_code() {
+                return None;
+            }
+
+            let forward = delimiter.value == open;
+            let (nested, value) = if forward {
+                (open, close)
+            } else {
+                (close, open)
+            };
+
+            return Some(Matcher {
+                forward,
+                target: Target::Bracket {
+                    nested,
+                    value,
+                    depth: 0,
+                },
+            });
+        }
+
+        let forward = if delimiter.is_string_punctuation("begin") {
+            true
+        } else if delimiter.is_string_punctuation("end") {
+            false
+        } else {
+            return None;
+        };
+
+        Some(Matcher {
+            forward,
+            target: Target::Quote {
+                punctuation: if forward { "end" } else { "begin" },
+                depth: delimiter.scope.len(),
+            },
+        })
+    }
+
+    // Whether the candidate matches the delimiter. Candidates must be
+    // provided in order, moving away from the delimiter.
+    fn matches(&mut self, candidate: &Delimiter) -> bool {
+        match self.target {
+            Target::Bracket {
+                nested,
+                value,
+                ref mut depth,
+            } => {
+                if !candidate.is_code() {
+                    return false;
+                }
+
+                if candidate.value == nested {
+                    *depth += 1;
+                } else if candidate.value == value {
+                    if *depth == 0 {
+                        return true;
+                    }
+                    *depth -= 1;
+                }
+
+                false
+            }
+            Target::Quote { punctuation, depth } => {
+                candidate.scope.len() == depth && candidate.is_string_punctuation(punctuation)
+            }
+        }
+    }
+}
+
+impl Buffer {
+    /// Finds the bracket or quote matching the one at the specified position.
+    ///
+    /// Brackets (`()`, `[]`, and `{}`) inside strings and comments are
+    /// ignored, using the scopes produced by tokenizing the buffer with its
+    /// syntax definition (or plain text, if it doesn't have one). Quotes are
+    /// matched with the other end of the string they delimit, and so require
+    /// a syntax definition that scopes strings.
+    ///
+    /// Returns `None` if there isn't a bracket or string delimiter at the
+    /// position, or if it's unmatched. The syntax set should be the one from
+    /// which the buffer's syntax definition was sourced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::Position;
+    /// use syntect::parsing::SyntaxSet;
+    ///
+    /// let syntax_set = SyntaxSet::load_defaults_newlines();
+    /// let mut buffer = Buffer::new();
+    /// buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+    /// buffer.insert("scribe(\"(\", [1]);");
+    ///
+    /// assert_eq!(
+    ///     buffer.matching_bracket(Position{ line: 0, offset: 6 }, &syntax_set).unwrap(),
+    ///     Some(Position{ line: 0, offset: 15 })
+    /// );
+    /// assert_eq!(
+    ///     buffer.matching_bracket(Position{ line: 0, offset: 9 }, &syntax_set).unwrap(),
+    ///     Some(Position{ line: 0, offset: 7 })
+    /// );
+    /// ```
+    pub fn matching_bracket(
+        &self,
+        position: Position,
+        syntax_set: &SyntaxSet,
+    ) -> Result<Option<Position>> {
+        let tokens = self.tokens(syntax_set);
+        let line = LineRange::new(position.line, position.line + 1);
+        let mut preceding = delimiters_for(&tokens, &line)?;
+        let index = match preceding.iter().position(|d| d.position == position) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let following = preceding.split_off(index + 1);
+        let mut matcher = match preceding.pop().as_ref().and_then(Matcher::new) {
+            Some(matcher) => matcher,
+            None => return Ok(None),
+        };
+
+        // Scan outward from the delimiter a line at a time, so that
+        // only the lines between it and its match are tokenized.
+        let (mut candidates, mut lines): (_, Box<dyn Iterator<Item = usize>>) = if matcher.forward {
+            (following, Box::new(position.line + 1..self.line_count()))
+        } else {
+            (preceding, Box::new((0..position.line).rev()))
+        };
+        loop {
+            if !matcher.forward {
+                candidates.reverse();
+            }
+            if let Some(delimiter) = candidates.iter().find(|d| matcher.matches(d)) {
+                return Ok(Some(delimiter.position));
+            }
+
+            candidates = match lines.next() {
+                Some(line) => delimiters_for(&tokens, &LineRange::new(line, line + 1))?,
+                None => return Ok(None),
+            };
+        }
+    }
+
+    // Tokenizes the buffer, collecting its bracket and quote graphemes.
+    pub(crate) fn delimiters(&self, syntax_set: &SyntaxSet) -> Result<Vec<Delimiter>> {
+        let tokens = self.tokens(syntax_set);
+        let mut iterator = tokens.iter()?;
+        let delimiters = collect_delimiters(&mut iterator);
+
+        match iterator.error.take() {
+            Some(error) => Err(error),
+            None => Ok(delimiters),
+        }
+    }
+}
+
+// Collects the bracket and quote graphemes for the specified lines.
+fn delimiters_for(tokens: &TokenSet, lines: &LineRange) -> Result<Vec<Delimiter>> {
+    let mut iterator = tokens.iter_lines(lines)?;
+    let delimiters = collect_delimiters(&mut iterator);
+
+    match iterator.error.take() {
+        Some(error) => Err(error),
+        None => Ok(delimiters),
+    }
+}
+
+// Collects the bracket and quote graphemes produced by the iterator.
+fn collect_delimiters(iterator: &mut TokenIterator) -> Vec<Delimiter> {
+    let mut delimiters = Vec::new();
+
+    for token in iterator {
+        if let Token::Lexeme(lexeme) = token {
+            for (offset, grapheme) in lexeme.value.graphemes(true).enumerate() {
+                let delimiter = QUOTES.contains(&grapheme)
+                    || BRACKETS
+                        .iter()
+                        .any(|&(open, close)| grapheme == open || grapheme == close);
+
+                if delimiter {
+                    delimiters.push(Delimiter {
+                        value: grapheme.to_string(),
+                        position: Position {
+                            line: lexeme.position.line,
+                            offset: lexeme.position.offset + offset,
+                        },
+                        scope: lexeme.scope.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    delimiters
+}
+
+// Finds the index of the delimiter matching the one at the specified index.
+pub(crate) fn matching_delimiter(delimiters: &[Delimiter], index: usize) -> Option<usize> {
+    let mut matcher = Matcher::new(&delimiters[index])?;
+    let mut candidates: Box<dyn Iterator<Item = usize>> = if matcher.forward {
+        Box::new(index + 1..delimiters.len())
+    } else {
+        Box::new((0..index).rev())
+    };
+
+    candidates.find(|&i| matcher.matches(&delimiters[i]))
+}
+
+// Whether the scope name is the prefix, or a descendant of it.
+fn has_prefix(scope: &str, prefix: &str) -> bool {
+    scope == prefix
+        || scope
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::{Buffer, Position};
+    use syntect::parsing::SyntaxSet;
+
+    #[test]
+    fn matching_bracket_skips_nested_pairs_across_lines() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert("fn main() {\n    if x { y(); }\n}\n");
+
+        assert_eq!(
+            buffer
+                .matching_bracket(
+                    Position {
+                        line: 0,
+                        offset: 10
+                    },
+                    &syntax_set
+                )
+                .unwrap(),
+            Some(Position { line: 2, offset: 0 })
+        );
+        assert_eq!(
+            buffer
+                .matching_bracket(Position { line: 2, offset: 0 }, &syntax_set)
+                .unwrap(),
+            Some(Position {
+                line: 0,
+                offset: 10
+            })
+        );
+    }
+
+    #[test]
+    fn matching_bracket_ignores_brackets_in_strings_and_comments() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert("f(\")\", // )\n  x)");
+
+        assert_eq!(
+            buffer
+                .matching_bracket(Position { line: 0, offset: 1 }, &syntax_set)
+                .unwrap(),
+            Some(Position { line: 1, offset: 3 })
+        );
+        assert_eq!(
+            buffer
+                .matching_bracket(Position { line: 0, offset: 3 }, &syntax_set)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn matching_bracket_scans_backward_through_multi_line_comments() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert("f(\n/*\n(\n*/\n)");
+
+        assert_eq!(
+            buffer
+                .matching_bracket(Position { line: 4, offset: 0 }, &syntax_set)
+                .unwrap(),
+            Some(Position { line: 0, offset: 1 })
+        );
+    }
+
+    #[test]
+    fn matching_bracket_matches_quotes_using_string_scopes() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert("let s = \"a\\\"b\";");
+
+        assert_eq!(
+            buffer
+                .matching_bracket(Position { line: 0, offset: 8 }, &syntax_set)
+                .unwrap(),
+            Some(Position {
+                line: 0,
+                offset: 13
+            })
+        );
+        assert_eq!(
+            buffer
+                .matching_bracket(
+                    Position {
+                        line: 0,
+                        offset: 13
+                    },
+                    &syntax_set
+                )
+                .unwrap(),
+            Some(Position { line: 0, offset: 8 })
+        );
+        // Escaped quotes aren't delimiters.
+        assert_eq!(
+            buffer
+                .matching_bracket(
+                    Position {
+                        line: 0,
+                        offset: 11
+                    },
+                    &syntax_set
+                )
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn matching_bracket_uses_plain_text_without_a_syntax_definition() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.insert("(scribe [library)");
+
+        assert_eq!(
+            buffer
+                .matching_bracket(Position { line: 0, offset: 0 }, &syntax_set)
+                .unwrap(),
+            Some(Position {
+                line: 0,
+                offset: 16
+            })
+        );
+        assert_eq!(
+            buffer
+                .matching_bracket(Position { line: 0, offset: 8 }, &syntax_set)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            buffer
+                .matching_bracket(Position { line: 0, offset: 1 }, &syntax_set)
+                .unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,379 @@
+use crate::buffer::{Buffer, Lexeme, LineRange, Token};
+use crate::errors::*;
+use syntect::parsing::SyntaxSet;
+
+impl Buffer {
+    /// Comments the lines using the line comment delimiter defined by the
+    /// buffer's syntax definition, or uncomments them if they're all already
+    /// commented. Delimiters are inserted at the lines' shared indentation
+    /// level; blank lines are left untouched. Like other `LineRange`
+    /// operations, the end line is excluded, and the change is undone as a
+    /// single operation.
+    ///
+    /// The syntax set is used to look up comment metadata; it should be the
+    /// one from which the buffer's syntax definition was sourced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::LineRange;
+    /// use syntect::parsing::SyntaxSet;
+    ///
+    /// let syntax_set = SyntaxSet::load_defaults_newlines();
+    /// let mut buffer = Buffer::new();
+    /// buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+    /// buffer.insert("fn main() {\n    scribe();\n}");
+    ///
+    /// buffer.toggle_comment(&LineRange::new(0, 3), &syntax_set).unwrap();
+    /// assert_eq!(buffer.data(), "// fn main() {\n//     scribe();\n// }");
+    ///
+    /// buffer.toggle_comment(&LineRange::new(0, 3), &syntax_set).unwrap();
+    /// assert_eq!(buffer.data(), "fn main() {\n    scribe();\n}");
+    /// ```
+    pub fn toggle_comment(&mut self, lines: &LineRange, syntax_set: &SyntaxSet) -> Result<()> {
+        let (token, _) = self.comment_delimiters(syntax_set, |metadata| {
+            metadata
+                .line_comment()
+                .map(|start| (start.to_string(), String::new()))
+        })?;
+        let token = token.as_str();
+        let delimiter = format!("{} ", token);
+
+        self.transform_lines(lines, |content| {
+            let commented = content
+                .iter()
+                .filter(|line| !line.trim().is_empty())
+                .all(|line| line.trim_start().starts_with(token));
+            let indent = content
+                .iter()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| indentation(line))
+                .reduce(shared_prefix)
+                .map_or(0, str::len);
+
+            for line in content.iter_mut().filter(|line| !line.trim().is_empty()) {
+                if commented {
+                    let indent = indentation(line).len();
+                    let rest = &line[indent + token.len()..];
+                    *line = format!("{}{}", &line[..indent], strip_space(rest));
+                } else {
+                    line.insert_str(indent, &delimiter);
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Wraps the lines in the block comment delimiters defined by the buffer's
+    /// syntax definition, or unwraps them if they're already wrapped. The
+    /// opening delimiter is inserted after the first non-blank line's
+    /// indentation, and the closing delimiter at the end of the last
+    /// non-blank line. Like other `LineRange` operations, the end line is
+    /// excluded, and the change is undone as a single operation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::LineRange;
+    /// use syntect::parsing::SyntaxSet;
+    ///
+    /// let syntax_set = SyntaxSet::load_defaults_newlines();
+    /// let mut buffer = Buffer::new();
+    /// buffer.syntax_definition = syntax_set.find_syntax_by_extension("css").cloned();
+    /// buffer.insert("  a {\n    color: red;\n  }");
+    ///
+    /// buffer.toggle_block_comment(&LineRange::new(0, 3), &syntax_set).unwrap();
+    /// assert_eq!(buffer.data(), "  /* a {\n    color: red;\n  } */");
+    ///
+    /// buffer.toggle_block_comment(&LineRange::new(0, 3), &syntax_set).unwrap();
+    /// assert_eq!(buffer.data(), "  a {\n    color: red;\n  }");
+    /// ```
+    pub fn toggle_block_comment(
+        &mut self,
+        lines: &LineRange,
+        syntax_set: &SyntaxSet,
+    ) -> Result<()> {
+        let (start, end) = self.comment_delimiters(syntax_set, |metadata| {
+            metadata
+                .block_comment()
+                .map(|(start, end)| (start.to_string(), end.to_string()))
+        })?;
+        let (start_token, end_token) = (start.as_str(), end.as_str());
+        let single_comment = self.spans_single_comment(lines, end_token, syntax_set)?;
+        let (start, end) = (format!("{} ", start), format!(" {}", end));
+
+        self.transform_lines(lines, |content| {
+            let first = match content.iter().position(|line| !line.trim().is_empty()) {
+                Some(index) => index,
+                None => return,
+            };
+            let last = content
+                .iter()
+                .rposition(|line| !line.trim().is_empty())
+                .unwrap_or(first);
+
+            let indent = indentation(&content[first]).len();
+            let commented = single_comment
+                && content[first][indent..].starts_with(start_token)
+                && content[last].trim_end().ends_with(end_token)
+                && (first != last
+                    || content[first].trim().len() >= start_token.len() + end_token.len());
+
+            if commented {
+                let line = &content[last];
+                let rest = &line[..line.trim_end().len() - end_token.len()];
+                content[last] = format!(
+                    "{}{}",
+                    rest.strip_suffix(' ').unwrap_or(rest),
+                    &line[line.trim_end().len()..]
+                );
+
+                let line = &content[first];
+                content[first] = format!(
+                    "{}{}",
+                    &line[..indent],
+                    strip_space(&line[indent + start_token.len()..])
+                );
+            } else {
+                let line = &content[last];
+                let trimmed = line.trim_end().len();
+                content[last] = format!("{}{}{}", &line[..trimmed], end, &line[trimmed..]);
+                content[first].insert_str(indent, &start);
+            }
+        });
+
+        Ok(())
+    }
+
+    // Whether the lines' content, ignoring surrounding whitespace, is a single
+    // comment, rather than several comments separated by code or whitespace.
+    fn spans_single_comment(
+        &self,
+        lines: &LineRange,
+        end_token: &str,
+        syntax_set: &SyntaxSet,
+    ) -> Result<bool> {
+        let tokens = self.tokens(syntax_set);
+        let mut iterator = tokens.iter_lines(lines)?;
+        let lexemes: Vec<Lexeme> = iterator
+            .by_ref()
+            .filter_map(|token| match token {
+                Token::Lexeme(lexeme) => Some(lexeme),
+                Token::Newline => None,
+            })
+            .collect();
+        if let Some(error) = iterator.error.take() {
+            return Err(error);
+        }
+
+        // Indentation and trailing whitespace fall outside of the comment.
+        let first = lexemes
+            .iter()
+            .position(|lexeme| !lexeme.value.trim().is_empty());
+        let last = lexemes
+            .iter()
+            .rposition(|lexeme| !lexeme.value.trim().is_empty());
+        let (first, last) = match (first, last) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Ok(false),
+        };
+        Ok(lexemes[first..=last]
+            .iter()
+            .enumerate()
+            .all(|(index, lexeme)| {
+                let in_comment = lexeme.scope.as_slice().iter().any(|scope| {
+                    let scope = scope.build_string();
+                    scope == "comment" || scope.starts_with("comment.")
+                });
+
+                // An earlier closing delimiter ends the comment before the range does.
+                in_comment && (first + index == last || !lexeme.value.contains(end_token))
+            }))
+    }
+
+    // Looks up comment delimiters for the buffer's syntax definition, trimming
+    // any padding they define; callers separate them from content themselves.
+    fn comment_delimiters<F>(&self, syntax_set: &SyntaxSet, lookup: F) -> Result<(String, String)>
+    where
+        F: FnOnce(&syntect::parsing::ScopedMetadata) -> Option<(String, String)>,
+    {
+        let syntax = self
+            .syntax_definition
+            .as_ref()
+            .ok_or(ErrorKind::MissingSyntax)?;
+        let metadata = syntax_set.metadata().metadata_for_scope(&[syntax.scope]);
+
+        lookup(&metadata)
+            .map(|(start, end)| (start.trim().to_string(), end.trim().to_string()))
+            .ok_or_else(|| ErrorKind::MissingComment.into())
+    }
+}
+
+// The leading whitespace of the line.
+fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+// The longest prefix common to both values. Lines can be indented using
+// different whitespace characters, so this is used to find an indentation
+// level that's valid across lines, rather than their shortest indentation.
+fn shared_prefix<'a>(value: &'a str, other: &str) -> &'a str {
+    let length = value
+        .chars()
+        .zip(other.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(character, _)| character.len_utf8())
+        .sum();
+
+    &value[..length]
+}
+
+// Removes a single leading space, which is conventionally
+// used to separate comment delimiters from their content.
+fn strip_space(content: &str) -> &str {
+    content.strip_prefix(' ').unwrap_or(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::{Buffer, LineRange, Position};
+    use crate::errors::*;
+    use syntect::parsing::SyntaxSet;
+
+    #[test]
+    fn toggle_comment_uses_shared_indentation_and_skips_blank_lines() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("py").cloned();
+        buffer.insert("  if scribe:\n\n    library()");
+        buffer
+            .toggle_comment(&LineRange::new(0, 3), &syntax_set)
+            .unwrap();
+
+        assert_eq!(buffer.data(), "  # if scribe:\n\n  #   library()");
+    }
+
+    #[test]
+    fn toggle_comments_handle_mixed_unicode_indentation() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("py").cloned();
+        buffer.insert("\u{a0}x\n \u{a0}y");
+        buffer
+            .toggle_comment(&LineRange::new(0, 2), &syntax_set)
+            .unwrap();
+        assert_eq!(buffer.data(), "# \u{a0}x\n#  \u{a0}y");
+
+        buffer
+            .toggle_comment(&LineRange::new(0, 2), &syntax_set)
+            .unwrap();
+        assert_eq!(buffer.data(), "\u{a0}x\n \u{a0}y");
+
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("css").cloned();
+        buffer.insert(" \u{a0}a {}\n\u{a0}b {}");
+        buffer
+            .toggle_block_comment(&LineRange::new(0, 2), &syntax_set)
+            .unwrap();
+        assert_eq!(buffer.data(), " \u{a0}/* a {}\n\u{a0}b {} */");
+    }
+
+    #[test]
+    fn toggle_comment_comments_partially_commented_lines() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert("// scribe\nlibrary");
+        buffer
+            .toggle_comment(&LineRange::new(0, 2), &syntax_set)
+            .unwrap();
+
+        assert_eq!(buffer.data(), "// // scribe\n// library");
+    }
+
+    #[test]
+    fn toggle_comment_uncomments_delimiters_without_spaces() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert("    //scribe");
+        buffer
+            .toggle_comment(&LineRange::new(0, 1), &syntax_set)
+            .unwrap();
+
+        assert_eq!(buffer.data(), "    scribe");
+    }
+
+    #[test]
+    fn toggle_comment_is_undone_as_a_single_operation() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert("scribe\nlibrary");
+        buffer.cursor.move_to(Position { line: 1, offset: 2 });
+        buffer
+            .toggle_comment(&LineRange::new(0, 2), &syntax_set)
+            .unwrap();
+        buffer.undo();
+
+        assert_eq!(buffer.data(), "scribe\nlibrary");
+    }
+
+    #[test]
+    fn toggle_comment_fails_without_syntax_definition() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe");
+
+        match buffer.toggle_comment(&LineRange::new(0, 1), &syntax_set) {
+            Err(Error(ErrorKind::MissingSyntax, _)) => (),
+            _ => panic!("expected a missing syntax error"),
+        }
+    }
+
+    #[test]
+    fn toggle_comment_fails_without_comment_delimiters() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = Some(syntax_set.find_syntax_plain_text().clone());
+        buffer.insert("scribe");
+
+        match buffer.toggle_comment(&LineRange::new(0, 1), &syntax_set) {
+            Err(Error(ErrorKind::MissingComment, _)) => (),
+            _ => panic!("expected a missing comment error"),
+        }
+        assert_eq!(buffer.data(), "scribe");
+    }
+
+    #[test]
+    fn toggle_block_comment_handles_a_single_line() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("c").cloned();
+        buffer.insert("scribe();\n");
+        buffer
+            .toggle_block_comment(&LineRange::new(0, 2), &syntax_set)
+            .unwrap();
+        assert_eq!(buffer.data(), "/* scribe(); */\n");
+
+        buffer
+            .toggle_block_comment(&LineRange::new(0, 2), &syntax_set)
+            .unwrap();
+        assert_eq!(buffer.data(), "scribe();\n");
+    }
+
+    #[test]
+    fn toggle_block_comment_wraps_separate_comments() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("c").cloned();
+        buffer.insert("/* a */\nb\n/* c */");
+        buffer
+            .toggle_block_comment(&LineRange::new(0, 3), &syntax_set)
+            .unwrap();
+        assert_eq!(buffer.data(), "/* /* a */\nb\n/* c */ */");
+    }
+}
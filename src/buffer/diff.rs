@@ -0,0 +1,260 @@
+use crate::buffer::{Buffer, Delete, Insert, Position, Range};
+
+// A contiguous change: replace `length` old lines starting
+// at `start` with new lines from `new_start..new_end`.
+#[derive(Debug, PartialEq)]
+struct Hunk {
+    start: usize,
+    length: usize,
+    new_start: usize,
+    new_end: usize,
+}
+
+impl Buffer {
+    // Replaces the range with the content, only modifying lines that differ.
+    // The changes are recorded as part of any open operation group.
+    pub(crate) fn replace_range_minimally(&mut self, range: &Range, old: &str, new: &str) {
+        let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+        let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+
+        // The position at the start of the specified old line.
+        let position = |line: usize| {
+            if line == 0 {
+                range.start()
+            } else if line == old_lines.len() {
+                range.end()
+            } else {
+                Position {
+                    line: range.start().line + line,
+                    offset: 0,
+                }
+            }
+        };
+
+        // Apply hunks in reverse, so that earlier positions remain valid.
+        for hunk in diff(&old_lines, &new_lines).into_iter().rev() {
+            let start = position(hunk.start);
+            if hunk.length > 0 {
+                let end = position(hunk.start + hunk.length);
+                self.apply_operation(Box::new(Delete::new(Range::new(start, end))));
+            }
+            if hunk.new_end > hunk.new_start {
+                let content = new_lines[hunk.new_start..hunk.new_end].concat();
+                self.apply_operation(Box::new(Insert::new(content, start)));
+            }
+        }
+    }
+}
+
+// Computes the changes required to turn the old lines into the new
+// ones, using Myers' algorithm to find a minimal edit script.
+fn diff(old: &[&str], new: &[&str]) -> Vec<Hunk> {
+    // Skip common leading and trailing lines, which are typical for
+    // localized changes, and are cheaper to handle here.
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let (mut old_index, mut new_index) = (prefix, prefix);
+    for edit in edit_script(old_middle, new_middle) {
+        let extends_last = hunks
+            .last()
+            .is_some_and(|h| h.start + h.length == old_index && h.new_end == new_index);
+        if !extends_last && edit != Edit::Equal {
+            hunks.push(Hunk {
+                start: old_index,
+                length: 0,
+                new_start: new_index,
+                new_end: new_index,
+            });
+        }
+
+        match edit {
+            Edit::Equal => {
+                old_index += 1;
+                new_index += 1;
+            }
+            Edit::Delete => {
+                if let Some(hunk) = hunks.last_mut() {
+                    hunk.length += 1;
+                }
+                old_index += 1;
+            }
+            Edit::Insert => {
+                if let Some(hunk) = hunks.last_mut() {
+                    hunk.new_end += 1;
+                }
+                new_index += 1;
+            }
+        }
+    }
+
+    hunks
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+// The maximum number of path entries retained while searching for an edit
+// script, beyond which we fall back to replacing all of the lines.
+const TRACE_LIMIT: usize = 1 << 22;
+
+// Finds the shortest sequence of line edits turning old into new.
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut furthest = vec![0isize; 2 * max + 3];
+    let mut trace = Vec::new();
+
+    // Find the furthest reaching path for each number of edits.
+    'search: for edits in 0..=max as isize {
+        if (trace.len() + 1) * furthest.len() > TRACE_LIMIT {
+            let mut script = vec![Edit::Delete; old.len()];
+            script.resize(old.len() + new.len(), Edit::Insert);
+
+            return script;
+        }
+        trace.push(furthest.clone());
+        for diagonal in (-edits..=edits).step_by(2) {
+            let index = (diagonal + offset) as usize;
+            let mut x = if diagonal == -edits
+                || (diagonal != edits && furthest[index - 1] < furthest[index + 1])
+            {
+                furthest[index + 1]
+            } else {
+                furthest[index - 1] + 1
+            };
+            let mut y = x - diagonal;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            furthest[index] = x;
+
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // Walk back through the trace to recover the edits.
+    let mut script = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (edits, furthest) in trace.iter().enumerate().rev() {
+        let edits = edits as isize;
+        let diagonal = x - y;
+        let previous_diagonal = if diagonal == -edits
+            || (diagonal != edits
+                && furthest[(diagonal - 1 + offset) as usize]
+                    < furthest[(diagonal + 1 + offset) as usize])
+        {
+            diagonal + 1
+        } else {
+            diagonal - 1
+        };
+        let previous_x = furthest[(previous_diagonal + offset) as usize];
+        let previous_y = previous_x - previous_diagonal;
+
+        while x > previous_x && y > previous_y {
+            script.push(Edit::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if edits > 0 {
+            script.push(if x == previous_x {
+                Edit::Insert
+            } else {
+                Edit::Delete
+            });
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    script.reverse();
+
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, edit_script, Edit, Hunk};
+    use crate::buffer::{Buffer, Position, Range};
+
+    #[test]
+    fn edit_script_finds_minimal_edits() {
+        let old = ["a", "b", "c", "a", "b", "b", "a"];
+        let new = ["c", "b", "a", "b", "a", "c"];
+        let script = edit_script(&old, &new);
+
+        assert_eq!(script.iter().filter(|&&e| e != Edit::Equal).count(), 5);
+        assert_eq!(script.iter().filter(|&&e| e != Edit::Insert).count(), 7);
+    }
+
+    #[test]
+    fn diff_groups_adjacent_edits_into_hunks() {
+        let old = ["a\n", "b\n", "c\n", "d\n"];
+        let new = ["a\n", "x\n", "y\n", "c\n", "d\n", "e"];
+
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Hunk {
+                    start: 1,
+                    length: 1,
+                    new_start: 1,
+                    new_end: 3
+                },
+                Hunk {
+                    start: 4,
+                    length: 0,
+                    new_start: 5,
+                    new_end: 6
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn replace_range_minimally_only_modifies_changed_lines() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary\neditor\n");
+        let range = Range::new(
+            Position { line: 0, offset: 3 },
+            Position { line: 3, offset: 0 },
+        );
+        let old = buffer.read(&range).unwrap();
+        buffer.replace_range_minimally(&range, &old, "ibe\nbook\neditor\nend\n");
+
+        assert_eq!(buffer.data(), "scribe\nbook\neditor\nend\n");
+    }
+
+    #[test]
+    fn replace_range_minimally_handles_empty_content() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe");
+        let range = Range::new(
+            Position { line: 0, offset: 0 },
+            Position { line: 0, offset: 6 },
+        );
+        buffer.replace_range_minimally(&range, "scribe", "");
+        assert_eq!(buffer.data(), "");
+
+        let range = Range::new(Position::new(), Position::new());
+        buffer.replace_range_minimally(&range, "", "library\n");
+        assert_eq!(buffer.data(), "library\n");
+    }
+}
@@ -25,7 +25,7 @@ impl Distance {
     pub fn of_str(from: &str) -> Distance {
         Distance {
             lines: from.chars().filter(|&c| c == '\n').count(),
-            offset: from.split('\n').last().map(|l| l.len()).unwrap_or(0),
+            offset: from.split('\n').next_back().map(|l| l.len()).unwrap_or(0),
         }
     }
 }
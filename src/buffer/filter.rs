@@ -0,0 +1,168 @@
+use crate::buffer::{Buffer, Position, Range};
+use crate::errors::*;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+impl Buffer {
+    /// Runs the command, writing the range's content (or that of the entire
+    /// buffer, if no range is provided) to its standard input, and replaces
+    /// the range with its standard output. Only lines that differ are
+    /// modified, as a single undoable operation, which allows the cursor
+    /// position to be retained across formatter runs.
+    ///
+    /// If the command exits unsuccessfully, the buffer is left untouched,
+    /// and an `ErrorKind::CommandFailed` error is returned, holding its
+    /// exit status and standard error output.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::{Position, Range};
+    /// use std::process::Command;
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("scribe\nlibrary\neditor\n");
+    /// # if cfg!(unix) {
+    /// buffer.filter_through(&mut Command::new("sort"), None).unwrap();
+    /// assert_eq!(buffer.data(), "editor\nlibrary\nscribe\n");
+    ///
+    /// buffer.filter_through(
+    ///     &mut Command::new("tr").args(["a-z", "A-Z"]),
+    ///     Some(Range::new(
+    ///         Position{ line: 1, offset: 0 },
+    ///         Position{ line: 1, offset: 3 }
+    ///     ))
+    /// ).unwrap();
+    /// assert_eq!(buffer.data(), "editor\nLIBrary\nscribe\n");
+    /// # }
+    /// ```
+    pub fn filter_through(&mut self, command: &mut Command, range: Option<Range>) -> Result<()> {
+        let range = range.unwrap_or_else(|| Range::new(Position::new(), self.end_position()));
+        let input = self.read(&range).ok_or(ErrorKind::InvalidRange)?;
+
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // Write input from another thread, so that a command
+        // producing lots of output doesn't block waiting on us.
+        let mut stdin = match child.stdin.take() {
+            Some(stdin) => stdin,
+            None => bail!("failed to open command input"),
+        };
+        let writer = thread::spawn(move || {
+            let result = stdin.write_all(input.as_bytes());
+            (input, result)
+        });
+
+        let output = child.wait_with_output()?;
+        let input = match writer.join() {
+            // Commands aren't required to read all of their input.
+            Ok((input, Ok(()))) => input,
+            Ok((input, Err(e))) if e.kind() == io::ErrorKind::BrokenPipe => input,
+            Ok((_, Err(e))) => return Err(e.into()),
+            Err(_) => bail!("failed to write command input"),
+        };
+
+        if !output.status.success() {
+            bail!(ErrorKind::CommandFailed(
+                output.status,
+                String::from_utf8_lossy(&output.stderr).into_owned()
+            ));
+        }
+        let output = String::from_utf8(output.stdout)?;
+
+        let cursor = self.cursor.position;
+        self.in_operation_group(|buffer| buffer.replace_range_minimally(&range, &input, &output));
+
+        if !self.cursor.move_to(cursor) {
+            self.cursor.move_to(Position {
+                line: cursor.line,
+                offset: 0,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::buffer::{Buffer, Position, Range};
+    use crate::errors::*;
+    use std::process::Command;
+
+    #[test]
+    fn filter_through_retains_cursor_and_unchanged_lines() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary\neditor");
+        buffer.cursor.move_to(Position { line: 2, offset: 3 });
+        buffer
+            .filter_through(Command::new("sed").arg("s/library/book/"), None)
+            .unwrap();
+
+        assert_eq!(buffer.data(), "scribe\nbook\neditor");
+        assert_eq!(*buffer.cursor, Position { line: 2, offset: 3 });
+
+        buffer.undo();
+        assert_eq!(buffer.data(), "scribe\nlibrary\neditor");
+    }
+
+    #[test]
+    fn filter_through_returns_status_and_stderr_on_failure() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe");
+        let result = buffer.filter_through(
+            Command::new("sh").args(["-c", "echo invalid input >&2; exit 3"]),
+            None,
+        );
+
+        match result {
+            Err(Error(ErrorKind::CommandFailed(status, stderr), _)) => {
+                assert_eq!(status.code(), Some(3));
+                assert_eq!(stderr, "invalid input\n");
+            }
+            _ => panic!("expected a command failure"),
+        }
+        assert_eq!(buffer.data(), "scribe");
+    }
+
+    #[test]
+    fn filter_through_rejects_invalid_ranges() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe");
+        let range = Range::new(Position::new(), Position { line: 2, offset: 0 });
+
+        match buffer.filter_through(&mut Command::new("cat"), Some(range)) {
+            Err(Error(ErrorKind::InvalidRange, _)) => (),
+            _ => panic!("expected an invalid range error"),
+        }
+    }
+
+    #[test]
+    fn filter_through_allows_commands_to_ignore_input() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\n".repeat(100_000));
+        buffer
+            .filter_through(Command::new("echo").arg("library"), None)
+            .unwrap();
+
+        assert_eq!(buffer.data(), "library\n");
+    }
+
+    #[test]
+    fn filter_through_handles_large_input() {
+        let mut buffer = Buffer::new();
+        let content = "scribe\n".repeat(100_000);
+        buffer.insert(content.clone());
+        buffer
+            .filter_through(&mut Command::new("cat"), None)
+            .unwrap();
+
+        assert_eq!(buffer.data(), content);
+    }
+}
@@ -2,7 +2,7 @@
 
 use super::Position;
 use super::Range;
-use std::borrow::Borrow;
+use std::borrow::{Borrow, Cow};
 use std::cmp::Ordering;
 use std::fmt;
 use unicode_segmentation::UnicodeSegmentation;
@@ -103,14 +103,8 @@ impl GapBuffer {
     /// ```
     pub fn read(&self, range: &Range) -> Option<String> {
         // Map positions to offsets in the buffer.
-        let start_offset = match self.find_offset(&range.start()) {
-            Some(offset) => offset,
-            None => return None,
-        };
-        let end_offset = match self.find_offset(&range.end()) {
-            Some(offset) => offset,
-            None => return None,
-        };
+        let start_offset = self.find_offset(&range.start())?;
+        let end_offset = self.find_offset(&range.end())?;
 
         let data = if start_offset < self.gap_start && self.gap_start < end_offset {
             // The gap is in the middle of the range being requested.
@@ -203,6 +197,77 @@ impl GapBuffer {
         self.find_offset(position).is_some()
     }
 
+    /// The length of the specified line, in grapheme clusters, excluding
+    /// its newline. Returns None if the line doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::buffer::GapBuffer;
+    ///
+    /// let buffer = GapBuffer::new("scribe\nनी library");
+    /// assert_eq!(buffer.line_length(1), Some(9));
+    /// assert_eq!(buffer.line_length(2), None);
+    /// ```
+    pub fn line_length(&self, line: usize) -> Option<usize> {
+        let mut current_line = 0;
+        let mut length = 0;
+
+        for half in self.halves().iter() {
+            for grapheme in half.graphemes(true) {
+                if grapheme == "\n" {
+                    if current_line == line {
+                        return Some(length);
+                    }
+                    current_line += 1;
+                } else if current_line == line {
+                    length += 1;
+                }
+            }
+        }
+
+        if current_line == line {
+            Some(length)
+        } else {
+            None
+        }
+    }
+
+    /// The position immediately following the buffer's last character.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::buffer::{GapBuffer, Position};
+    ///
+    /// let buffer = GapBuffer::new("scribe\nनी library");
+    /// assert_eq!(buffer.end_position(), Position{ line: 1, offset: 9 });
+    /// ```
+    pub fn end_position(&self) -> Position {
+        let mut position = Position { line: 0, offset: 0 };
+
+        for half in self.halves().iter() {
+            for grapheme in half.graphemes(true) {
+                if grapheme == "\n" {
+                    position.line += 1;
+                    position.offset = 0;
+                } else {
+                    position.offset += 1;
+                }
+            }
+        }
+
+        position
+    }
+
+    // The data on either side of the gap.
+    fn halves(&self) -> [Cow<'_, str>; 2] {
+        [
+            String::from_utf8_lossy(&self.data[..self.gap_start]),
+            String::from_utf8_lossy(&self.data[self.gap_start + self.gap_length..]),
+        ]
+    }
+
     // Maps a position to its offset equivalent in the data.
     fn find_offset(&self, position: &Position) -> Option<usize> {
         let first_half = String::from_utf8_lossy(&self.data[..self.gap_start]);
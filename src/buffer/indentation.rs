@@ -0,0 +1,426 @@
+use crate::buffer::{Buffer, LineRange, Position};
+use std::collections::HashMap;
+use syntect::parsing::SyntaxSet;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The characters used to indent lines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IndentStyle {
+    Tabs,
+    Spaces,
+}
+
+/// A buffer's indentation settings. For space-based indentation, `width` is the
+/// number of spaces in each indentation level; for tabs, it's the number of
+/// columns a tab is considered to occupy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Indentation {
+    pub style: IndentStyle,
+    pub width: usize,
+}
+
+impl Default for Indentation {
+    fn default() -> Self {
+        Indentation {
+            style: IndentStyle::Spaces,
+            width: 4,
+        }
+    }
+}
+
+impl Indentation {
+    /// Infers indentation settings from the content, based on whether its
+    /// indented lines predominantly use tabs or spaces, and for the latter,
+    /// the most common change in indentation between consecutive lines.
+    /// Returns None if the content has no indented lines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::buffer::{Indentation, IndentStyle};
+    ///
+    /// let indentation = Indentation::detect("fn main() {\n  if scribe {\n    library();\n  }\n}");
+    /// assert_eq!(indentation, Some(Indentation{ style: IndentStyle::Spaces, width: 2 }));
+    ///
+    /// let indentation = Indentation::detect("fn main() {\n\tscribe();\n}").unwrap();
+    /// assert_eq!(indentation.style, IndentStyle::Tabs);
+    ///
+    /// assert!(Indentation::detect("scribe").is_none());
+    /// ```
+    pub fn detect(content: &str) -> Option<Indentation> {
+        let mut tab_lines = 0;
+        let mut space_lines = 0;
+        let mut widths = HashMap::new();
+        let mut previous_width = Some(0);
+
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            if line.starts_with('\t') {
+                // The next space-indented line can't be compared to this one.
+                tab_lines += 1;
+                previous_width = None;
+                continue;
+            }
+
+            let width = line.len() - line.trim_start_matches(' ').len();
+            if width > 0 {
+                space_lines += 1;
+            }
+            if let Some(previous_width) = previous_width {
+                if width > previous_width {
+                    *widths.entry(width - previous_width).or_insert(0) += 1;
+                }
+            }
+            previous_width = Some(width);
+        }
+
+        if tab_lines == 0 && space_lines == 0 {
+            return None;
+        }
+
+        if tab_lines > space_lines {
+            return Some(Indentation {
+                style: IndentStyle::Tabs,
+                ..Default::default()
+            });
+        }
+
+        // Prefer the smaller width when frequencies are tied, since
+        // larger ones are often multiples of the actual width.
+        let width = widths
+            .into_iter()
+            .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(b.cmp(a)))
+            .map(|(width, _)| width)?;
+
+        Some(Indentation {
+            style: IndentStyle::Spaces,
+            width,
+        })
+    }
+
+    /// The content of a single indentation level.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::buffer::{Indentation, IndentStyle};
+    ///
+    /// let indentation = Indentation{ style: IndentStyle::Spaces, width: 2 };
+    /// assert_eq!(indentation.unit(), "  ");
+    /// ```
+    pub fn unit(&self) -> String {
+        match self.style {
+            IndentStyle::Tabs => String::from("\t"),
+            IndentStyle::Spaces => " ".repeat(self.width),
+        }
+    }
+}
+
+impl Buffer {
+    /// Inserts a newline at the cursor, carrying over the current line's
+    /// indentation to the new line and moving the cursor to the end of it.
+    /// If the buffer's syntax definition defines indentation patterns in the
+    /// syntax set's metadata, the new line is indented an extra level when
+    /// the content before the cursor matches the increase pattern (e.g. an
+    /// opening brace), and a level less when the content after the cursor
+    /// matches the decrease pattern (e.g. a closing brace). When both match
+    /// (e.g. between a pair of braces), the content after the cursor is moved
+    /// to its own line, at the current line's indentation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::Position;
+    /// use syntect::parsing::SyntaxSet;
+    ///
+    /// let syntax_set = SyntaxSet::load_defaults_newlines();
+    /// let mut buffer = Buffer::new();
+    /// buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+    /// buffer.insert("fn main() {\n    scribe();");
+    /// buffer.cursor.move_to(Position{ line: 1, offset: 13 });
+    ///
+    /// buffer.insert_newline(&syntax_set);
+    /// assert_eq!(buffer.data(), "fn main() {\n    scribe();\n    ");
+    /// assert_eq!(*buffer.cursor, Position{ line: 2, offset: 4 });
+    /// ```
+    pub fn insert_newline(&mut self, syntax_set: &SyntaxSet) {
+        let position = self.cursor.position;
+        let line = self
+            .data()
+            .split('\n')
+            .nth(position.line)
+            .unwrap_or_default()
+            .to_string();
+        let split = line
+            .grapheme_indices(true)
+            .nth(position.offset)
+            .map(|(index, _)| index)
+            .unwrap_or(line.len());
+        let (before, after) = line.split_at(split);
+
+        let mut indent = before[..before.len() - before.trim_start().len()].to_string();
+        let mut closing_indent = None;
+        if let Some(ref syntax) = self.syntax_definition {
+            let metadata = syntax_set.metadata().metadata_for_scope(&[syntax.scope]);
+            let increase = metadata.increase_indent(before) || metadata.bracket_increase(before);
+            let decrease = metadata.decrease_indent(after);
+
+            if increase && decrease {
+                // Split the pair, moving the closer to its own line.
+                closing_indent = Some(indent.clone());
+                indent.push_str(&self.indentation.unit());
+            } else if increase {
+                indent.push_str(&self.indentation.unit());
+            } else if decrease {
+                indent = outdented(&indent, &self.indentation).to_string();
+            }
+        }
+
+        match closing_indent {
+            Some(closing_indent) => self.insert(format!("\n{}\n{}", indent, closing_indent)),
+            None => self.insert(format!("\n{}", indent)),
+        }
+        self.cursor.move_to(Position {
+            line: position.line + 1,
+            offset: indent.graphemes(true).count(),
+        });
+    }
+
+    /// Indents the lines by a single level, using the buffer's indentation
+    /// settings. Blank lines are left untouched. Like other `LineRange`
+    /// operations, the end line is excluded, and the change is undone as a
+    /// single operation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::{LineRange, Position};
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("scribe\n\nlibrary");
+    /// buffer.cursor.move_to(Position{ line: 2, offset: 1 });
+    /// buffer.indent(&LineRange::new(0, 3));
+    ///
+    /// assert_eq!(buffer.data(), "    scribe\n\n    library");
+    /// assert_eq!(*buffer.cursor, Position{ line: 2, offset: 5 });
+    /// ```
+    pub fn indent(&mut self, lines: &LineRange) {
+        let unit = self.indentation.unit();
+        let cursor_line = self.cursor.line;
+        let cursor_indented = lines.includes(cursor_line)
+            && self
+                .data()
+                .split('\n')
+                .nth(cursor_line)
+                .is_some_and(|line| !line.trim().is_empty());
+
+        self.transform_lines(lines, |content| {
+            for line in content.iter_mut().filter(|line| !line.trim().is_empty()) {
+                line.insert_str(0, &unit);
+            }
+        });
+
+        // Keep the cursor on the same content, if its line was indented.
+        if cursor_indented
+            && !self.cursor.move_to(Position {
+                line: cursor_line,
+                offset: self.cursor.offset + unit.len(),
+            })
+        {
+            self.cursor.move_to_end_of_line();
+        }
+    }
+
+    /// Removes a single level of indentation from the lines, using the
+    /// buffer's indentation settings. A leading tab counts as a full level,
+    /// regardless of style. Like other `LineRange` operations, the end line
+    /// is excluded, and the change is undone as a single operation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::LineRange;
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("      scribe\n\tlibrary\n  editor");
+    /// buffer.outdent(&LineRange::new(0, 3));
+    ///
+    /// assert_eq!(buffer.data(), "  scribe\nlibrary\neditor");
+    /// ```
+    pub fn outdent(&mut self, lines: &LineRange) {
+        let indentation = self.indentation;
+        let cursor = self.cursor.position;
+        let mut removed = 0;
+
+        self.transform_lines(lines, |content| {
+            for (line_number, line) in (lines.start()..).zip(content.iter_mut()) {
+                let outdented = outdented(line, &indentation).to_string();
+                if line_number == cursor.line {
+                    removed = line.len() - outdented.len();
+                }
+                *line = outdented;
+            }
+        });
+
+        if removed > 0 {
+            self.cursor.move_to(Position {
+                line: cursor.line,
+                offset: cursor.offset.saturating_sub(removed),
+            });
+        }
+    }
+}
+
+// Removes a single level of indentation from the start of the content.
+fn outdented<'a>(content: &'a str, indentation: &Indentation) -> &'a str {
+    if let Some(rest) = content.strip_prefix('\t') {
+        return rest;
+    }
+
+    let spaces = content.len() - content.trim_start_matches(' ').len();
+    &content[spaces.min(indentation.width)..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IndentStyle, Indentation};
+    use crate::buffer::{Buffer, LineRange, Position};
+    use std::path::Path;
+    use syntect::parsing::SyntaxSet;
+
+    #[test]
+    fn detect_ignores_blank_lines_and_nesting_depth() {
+        let content = "a:\n    b:\n\n        c\n    d\ne:\n    f\n";
+
+        assert_eq!(
+            Indentation::detect(content),
+            Some(Indentation {
+                style: IndentStyle::Spaces,
+                width: 4
+            })
+        );
+    }
+
+    #[test]
+    fn detect_ignores_space_indentation_changes_across_tab_lines() {
+        let content = "a\n  b\n\tc\n      d\n  e\n\tf\n      g\n";
+
+        assert_eq!(
+            Indentation::detect(content),
+            Some(Indentation {
+                style: IndentStyle::Spaces,
+                width: 2
+            })
+        );
+    }
+
+    #[test]
+    fn from_file_detects_indentation() {
+        let buffer = Buffer::from_file(Path::new("tests/sample/indented")).unwrap();
+
+        assert_eq!(
+            buffer.indentation,
+            Indentation {
+                style: IndentStyle::Spaces,
+                width: 2
+            }
+        );
+    }
+
+    #[test]
+    fn from_file_uses_default_indentation_for_unindented_content() {
+        let buffer = Buffer::from_file(Path::new("tests/sample/file")).unwrap();
+
+        assert_eq!(buffer.indentation, Indentation::default());
+    }
+
+    #[test]
+    fn insert_newline_increases_indentation_after_opening_brace() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.indentation = Indentation {
+            style: IndentStyle::Tabs,
+            width: 4,
+        };
+        buffer.insert("\tfn main() {");
+        buffer.cursor.move_to(Position {
+            line: 0,
+            offset: 12,
+        });
+        buffer.insert_newline(&syntax_set);
+
+        assert_eq!(buffer.data(), "\tfn main() {\n\t\t");
+        assert_eq!(*buffer.cursor, Position { line: 1, offset: 2 });
+    }
+
+    #[test]
+    fn insert_newline_decreases_indentation_before_closing_brace() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert("fn main() {\n    scribe(); }");
+        buffer.cursor.move_to(Position {
+            line: 1,
+            offset: 14,
+        });
+        buffer.insert_newline(&syntax_set);
+
+        assert_eq!(buffer.data(), "fn main() {\n    scribe(); \n}");
+        assert_eq!(*buffer.cursor, Position { line: 2, offset: 0 });
+    }
+
+    #[test]
+    fn insert_newline_splits_brace_pairs() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert("    fn main() {}");
+        buffer.cursor.move_to(Position {
+            line: 0,
+            offset: 15,
+        });
+        buffer.insert_newline(&syntax_set);
+
+        assert_eq!(buffer.data(), "    fn main() {\n        \n    }");
+        assert_eq!(*buffer.cursor, Position { line: 1, offset: 8 });
+    }
+
+    #[test]
+    fn insert_newline_carries_over_indentation_without_syntax_definition() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.insert("  scribe {");
+        buffer.cursor.move_to(Position { line: 0, offset: 5 });
+        buffer.insert_newline(&syntax_set);
+
+        assert_eq!(buffer.data(), "  scr\n  ibe {");
+        assert_eq!(*buffer.cursor, Position { line: 1, offset: 2 });
+
+        buffer.undo();
+        assert_eq!(buffer.data(), "  scribe {");
+    }
+
+    #[test]
+    fn indent_is_undone_as_a_single_operation() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary");
+        buffer.indent(&LineRange::new(0, 2));
+        buffer.undo();
+
+        assert_eq!(buffer.data(), "scribe\nlibrary");
+    }
+
+    #[test]
+    fn outdent_moves_cursor_with_content() {
+        let mut buffer = Buffer::new();
+        buffer.insert("    scribe");
+        buffer.cursor.move_to(Position { line: 0, offset: 6 });
+        buffer.outdent(&LineRange::new(0, 1));
+
+        assert_eq!(buffer.data(), "scribe");
+        assert_eq!(*buffer.cursor, Position { line: 0, offset: 2 });
+    }
+}
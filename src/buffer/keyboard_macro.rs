@@ -0,0 +1,506 @@
+//! Recordable, replayable sequences of buffer edits and cursor movements.
+
+use crate::buffer::{Buffer, LineRange, Position};
+use crate::errors::*;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A cursor movement that can be recorded as part of a macro.
+/// Each variant corresponds to a `Cursor` method of the same name.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Motion {
+    Up,
+    Down,
+    Left,
+    Right,
+    StartOfLine,
+    EndOfLine,
+    FirstLine,
+    LastLine,
+    To(Position),
+}
+
+/// A single primitive action performed as part of a macro.
+///
+/// Edits are relative to the cursor position at the time they're replayed,
+/// mirroring the `Buffer::insert` and `Buffer::delete` methods.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MacroStep {
+    Insert(String),
+    Delete,
+    Move(Motion),
+}
+
+/// A recorded sequence of buffer edits and cursor movements.
+///
+/// Macros are recorded using `Buffer::start_macro_recording`, and replayed
+/// using `Buffer::replay_macro` or `Buffer::replay_macro_at_lines`. They can
+/// be persisted to disk using a simple line-based format, one step per line:
+///
+/// ```text
+/// move start_of_line
+/// insert "// "
+/// move down
+/// ```
+///
+/// Inserted content is quoted, with backslashes, quotes, and newline, tab,
+/// and carriage return characters escaped. Absolute movements are written as
+/// `move to <line> <offset>`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Macro {
+    steps: Vec<MacroStep>,
+}
+
+impl Macro {
+    /// Creates a new empty macro.
+    pub fn new() -> Macro {
+        Default::default()
+    }
+
+    /// Reads and parses a macro from the specified path.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::buffer::{Macro, MacroStep, Motion};
+    /// # use std::env;
+    ///
+    /// let mut recorded = Macro::new();
+    /// recorded.push(MacroStep::Insert("scribe".to_string()));
+    /// recorded.push(MacroStep::Move(Motion::Down));
+    ///
+    /// let path = env::temp_dir().join("scribe_macro_example");
+    /// recorded.save(&path).unwrap();
+    /// assert_eq!(Macro::from_file(&path).unwrap(), recorded);
+    /// # std::fs::remove_file(&path);
+    /// ```
+    pub fn from_file(path: &Path) -> Result<Macro> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Writes the macro to the specified path.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_string())?;
+
+        Ok(())
+    }
+
+    /// Appends a step to the macro.
+    pub fn push(&mut self, step: MacroStep) {
+        self.steps.push(step);
+    }
+
+    /// The macro's steps, in the order in which they're replayed.
+    pub fn steps(&self) -> &[MacroStep] {
+        &self.steps
+    }
+
+    /// Whether or not the macro contains any steps.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+impl fmt::Display for MacroStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MacroStep::Insert(ref content) => write!(f, "insert \"{}\"", escape(content)),
+            MacroStep::Delete => write!(f, "delete"),
+            MacroStep::Move(Motion::Up) => write!(f, "move up"),
+            MacroStep::Move(Motion::Down) => write!(f, "move down"),
+            MacroStep::Move(Motion::Left) => write!(f, "move left"),
+            MacroStep::Move(Motion::Right) => write!(f, "move right"),
+            MacroStep::Move(Motion::StartOfLine) => write!(f, "move start_of_line"),
+            MacroStep::Move(Motion::EndOfLine) => write!(f, "move end_of_line"),
+            MacroStep::Move(Motion::FirstLine) => write!(f, "move first_line"),
+            MacroStep::Move(Motion::LastLine) => write!(f, "move last_line"),
+            MacroStep::Move(Motion::To(position)) => {
+                write!(f, "move to {} {}", position.line, position.offset)
+            }
+        }
+    }
+}
+
+impl FromStr for MacroStep {
+    type Err = Error;
+
+    fn from_str(step: &str) -> Result<MacroStep> {
+        if let Some(content) = step.strip_prefix("insert ") {
+            return content
+                .strip_prefix('"')
+                .and_then(|c| c.strip_suffix('"'))
+                .and_then(unescape)
+                .map(MacroStep::Insert)
+                .ok_or_else(|| ErrorKind::InvalidMacroStep(step.to_string()).into());
+        }
+
+        let motion = match step.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["delete"] => return Ok(MacroStep::Delete),
+            ["move", "up"] => Motion::Up,
+            ["move", "down"] => Motion::Down,
+            ["move", "left"] => Motion::Left,
+            ["move", "right"] => Motion::Right,
+            ["move", "start_of_line"] => Motion::StartOfLine,
+            ["move", "end_of_line"] => Motion::EndOfLine,
+            ["move", "first_line"] => Motion::FirstLine,
+            ["move", "last_line"] => Motion::LastLine,
+            ["move", "to", line, offset] => match (line.parse(), offset.parse()) {
+                (Ok(line), Ok(offset)) => Motion::To(Position { line, offset }),
+                _ => bail!(ErrorKind::InvalidMacroStep(step.to_string())),
+            },
+            _ => bail!(ErrorKind::InvalidMacroStep(step.to_string())),
+        };
+
+        Ok(MacroStep::Move(motion))
+    }
+}
+
+impl fmt::Display for Macro {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{}", step)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Macro {
+    type Err = Error;
+
+    /// Parses a macro, one step per line, ignoring blank lines.
+    fn from_str(data: &str) -> Result<Macro> {
+        let steps = data
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.trim().parse())
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Macro { steps })
+    }
+}
+
+impl Buffer {
+    /// Starts recording edits made using `insert` and `delete`, along with
+    /// cursor movements made using `move_cursor`, into a new macro. Any
+    /// in-progress recording is discarded.
+    ///
+    /// Other edits (e.g. `delete_range`, `replace`, pasting, and line or case
+    /// commands) can't be replayed relative to the cursor, so they end the
+    /// recording, discarding it; use `recording_macro` to detect this. Steps
+    /// replayed from other macros during the recording aren't recorded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::Motion;
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("scribe\nlibrary");
+    ///
+    /// // Record a macro that comments out a line and moves to the next.
+    /// buffer.start_macro_recording();
+    /// buffer.move_cursor(Motion::StartOfLine);
+    /// buffer.insert("# ");
+    /// buffer.move_cursor(Motion::Down);
+    /// let recorded = buffer.stop_macro_recording().unwrap();
+    ///
+    /// // Replay it on the next line.
+    /// buffer.replay_macro(&recorded, 1);
+    /// assert_eq!(buffer.data(), "# scribe\n# library");
+    /// ```
+    pub fn start_macro_recording(&mut self) {
+        self.macro_recording = Some(Macro::new());
+    }
+
+    /// Stops recording, returning the recorded macro, if any. Returns `None`
+    /// if the recording was ended by an edit that can't be replayed.
+    pub fn stop_macro_recording(&mut self) -> Option<Macro> {
+        self.macro_recording.take()
+    }
+
+    /// Whether or not a macro is currently being recorded.
+    pub fn recording_macro(&self) -> bool {
+        self.macro_recording.is_some()
+    }
+
+    /// Moves the cursor, recording the movement if a macro is being recorded.
+    /// Movements are bounds-checked, as with their `Cursor` equivalents.
+    pub fn move_cursor(&mut self, motion: Motion) {
+        match motion {
+            Motion::Up => self.cursor.move_up(),
+            Motion::Down => self.cursor.move_down(),
+            Motion::Left => self.cursor.move_left(),
+            Motion::Right => self.cursor.move_right(),
+            Motion::StartOfLine => self.cursor.move_to_start_of_line(),
+            Motion::EndOfLine => self.cursor.move_to_end_of_line(),
+            Motion::FirstLine => self.cursor.move_to_first_line(),
+            Motion::LastLine => self.cursor.move_to_last_line(),
+            Motion::To(position) => {
+                self.cursor.move_to(position);
+            }
+        }
+
+        self.record_macro_step(MacroStep::Move(motion));
+    }
+
+    /// Replays a macro `count` times, starting at the cursor position.
+    /// All replays are grouped into a single undoable operation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::{Macro, MacroStep, Motion};
+    ///
+    /// let mut typing = Macro::new();
+    /// typing.push(MacroStep::Insert("a".to_string()));
+    /// typing.push(MacroStep::Move(Motion::Right));
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.replay_macro(&typing, 3);
+    /// assert_eq!(buffer.data(), "aaa");
+    ///
+    /// buffer.undo();
+    /// assert_eq!(buffer.data(), "");
+    /// ```
+    pub fn replay_macro(&mut self, recorded: &Macro, count: usize) {
+        self.in_operation_group(|buffer| {
+            for _ in 0..count {
+                buffer.run_macro_steps(recorded);
+            }
+        });
+    }
+
+    /// Replays a macro once at the start of every line in the range,
+    /// accounting for lines added or removed by previous replays.
+    /// All replays are grouped into a single undoable operation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::{LineRange, Macro, MacroStep};
+    ///
+    /// let mut comment = Macro::new();
+    /// comment.push(MacroStep::Insert("// ".to_string()));
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("scribe\nlibrary\neditor");
+    /// buffer.replay_macro_at_lines(&comment, &LineRange::new(0, 2));
+    /// assert_eq!(buffer.data(), "// scribe\n// library\neditor");
+    /// ```
+    pub fn replay_macro_at_lines(&mut self, recorded: &Macro, lines: &LineRange) {
+        let mut line = lines.start();
+        let mut end = lines.end();
+
+        self.in_operation_group(|buffer| {
+            while line < end {
+                if !buffer.cursor.move_to(Position { line, offset: 0 }) {
+                    break;
+                }
+
+                // Shift the remaining lines by any added by the replay; lines
+                // removed by it are considered consumed, shortening the range.
+                let line_count = buffer.line_count();
+                buffer.run_macro_steps(recorded);
+                let new_line_count = buffer.line_count();
+                if new_line_count >= line_count {
+                    let added = new_line_count - line_count;
+                    line += added;
+                    end += added;
+                } else {
+                    end = end.saturating_sub(line_count - new_line_count);
+                }
+
+                line += 1;
+            }
+        });
+    }
+
+    pub(crate) fn record_macro_step(&mut self, step: MacroStep) {
+        if let Some(ref mut recording) = self.macro_recording {
+            recording.push(step);
+        }
+    }
+
+    fn run_macro_steps(&mut self, recorded: &Macro) {
+        // Suspend any in-progress recording, so that it doesn't capture
+        // the steps being replayed (which record themselves when run).
+        let macro_recording = self.macro_recording.take();
+        for step in recorded.steps() {
+            match *step {
+                MacroStep::Insert(ref content) => self.insert(content.as_str()),
+                MacroStep::Delete => self.delete(),
+                MacroStep::Move(motion) => self.move_cursor(motion),
+            }
+        }
+        self.macro_recording = macro_recording;
+    }
+}
+
+// Escapes content so that it can be quoted on a single line.
+fn escape(content: &str) -> String {
+    let mut escaped = String::with_capacity(content.len());
+    for c in content.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+// Reverses escape, returning None for invalid or unquoted sequences.
+fn unescape(content: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(content.len());
+    let mut chars = content.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(match chars.next()? {
+                '\\' => '\\',
+                '"' => '"',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                _ => return None,
+            }),
+            '"' => return None,
+            _ => unescaped.push(c),
+        }
+    }
+
+    Some(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Macro, MacroStep, Motion};
+    use crate::buffer::{Buffer, LineRange, Position, Range};
+
+    #[test]
+    fn recording_captures_inserts_deletes_and_cursor_movements() {
+        let mut buffer = Buffer::new();
+        buffer.start_macro_recording();
+        buffer.insert("scribe");
+        buffer.move_cursor(Motion::EndOfLine);
+        buffer.delete();
+        buffer.cursor.move_to(Position::new());
+        let recorded = buffer.stop_macro_recording().unwrap();
+
+        assert_eq!(
+            recorded.steps(),
+            [
+                MacroStep::Insert("scribe".to_string()),
+                MacroStep::Move(Motion::EndOfLine),
+                MacroStep::Delete,
+            ]
+        );
+        assert!(!buffer.recording_macro());
+    }
+
+    #[test]
+    fn recording_is_discarded_by_edits_that_cannot_be_replayed() {
+        let mut buffer = Buffer::new();
+        buffer.start_macro_recording();
+        buffer.insert("scribe\nlibrary");
+        buffer.delete_range(Range::new(Position::new(), Position { line: 0, offset: 1 }));
+        assert!(!buffer.recording_macro());
+        assert!(buffer.stop_macro_recording().is_none());
+
+        buffer.start_macro_recording();
+        buffer.delete_lines(&LineRange::new(0, 1));
+        assert!(buffer.stop_macro_recording().is_none());
+
+        // Adjustments made when saving aren't user edits.
+        buffer.save_options.insert_final_newline = true;
+        buffer.start_macro_recording();
+        buffer.apply_save_options();
+        assert_eq!(buffer.data(), "library\n");
+        assert!(buffer.recording_macro());
+    }
+
+    #[test]
+    fn recording_does_not_capture_replayed_steps() {
+        let mut typing = Macro::new();
+        typing.push(MacroStep::Insert("a".to_string()));
+
+        let mut buffer = Buffer::new();
+        buffer.start_macro_recording();
+        buffer.replay_macro(&typing, 2);
+        buffer.move_cursor(Motion::EndOfLine);
+        let recorded = buffer.stop_macro_recording().unwrap();
+
+        assert_eq!(buffer.data(), "aa");
+        assert_eq!(recorded.steps(), [MacroStep::Move(Motion::EndOfLine)]);
+    }
+
+    #[test]
+    fn replay_macro_at_lines_accounts_for_inserted_lines() {
+        let mut duplicate = Macro::new();
+        duplicate.push(MacroStep::Insert("copy\n".to_string()));
+
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary\neditor");
+        buffer.replay_macro_at_lines(&duplicate, &LineRange::new(0, 2));
+
+        assert_eq!(buffer.data(), "copy\nscribe\ncopy\nlibrary\neditor");
+    }
+
+    #[test]
+    fn replay_macro_at_lines_accounts_for_removed_lines() {
+        let mut join = Macro::new();
+        join.push(MacroStep::Move(Motion::EndOfLine));
+        join.push(MacroStep::Delete);
+
+        let mut buffer = Buffer::new();
+        buffer.insert("a\nb\nc\nd\ne");
+        buffer.replay_macro_at_lines(&join, &LineRange::new(0, 4));
+
+        assert_eq!(buffer.data(), "ab\ncd\ne");
+    }
+
+    #[test]
+    fn replays_are_undone_as_a_single_operation() {
+        let mut comment = Macro::new();
+        comment.push(MacroStep::Insert("# ".to_string()));
+
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary");
+        buffer.replay_macro_at_lines(&comment, &LineRange::new(0, 2));
+        buffer.undo();
+
+        assert_eq!(buffer.data(), "scribe\nlibrary");
+    }
+
+    #[test]
+    fn macros_round_trip_through_their_string_representation() {
+        let mut recorded = Macro::new();
+        recorded.push(MacroStep::Insert(
+            "\"quoted\"\n\tand \\ escaped".to_string(),
+        ));
+        recorded.push(MacroStep::Delete);
+        recorded.push(MacroStep::Move(Motion::StartOfLine));
+        recorded.push(MacroStep::Move(Motion::To(Position { line: 2, offset: 3 })));
+
+        let serialized = recorded.to_string();
+        assert_eq!(serialized.lines().count(), 4);
+        assert_eq!(serialized.parse::<Macro>().unwrap(), recorded);
+    }
+
+    #[test]
+    fn parsing_rejects_invalid_steps() {
+        assert!("move sideways".parse::<Macro>().is_err());
+        assert!("insert unquoted".parse::<Macro>().is_err());
+        assert!("insert \"bad \\q escape\"".parse::<Macro>().is_err());
+        assert!("move to 1".parse::<Macro>().is_err());
+    }
+}
@@ -0,0 +1,573 @@
+use crate::buffer::{Buffer, Delete, Insert, LineRange, Position, Range};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Options controlling how `Buffer::sort_lines` compares lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SortOptions {
+    /// Compare lines by their leading number, if present. Lines without
+    /// one sort before those with one, and ties fall back to text order.
+    pub numeric: bool,
+
+    /// Ignore case when comparing line text.
+    pub case_insensitive: bool,
+}
+
+impl Buffer {
+    /// Inserts a copy of the lines below them, moving the cursor to the same
+    /// relative position in the copy. Like other `LineRange` operations, the
+    /// end line is excluded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::{LineRange, Position};
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("scribe\nlibrary");
+    /// buffer.cursor.move_to(Position{ line: 0, offset: 2 });
+    /// buffer.duplicate_lines(&LineRange::new(0, 1));
+    ///
+    /// assert_eq!(buffer.data(), "scribe\nscribe\nlibrary");
+    /// assert_eq!(*buffer.cursor, Position{ line: 1, offset: 2 });
+    /// ```
+    pub fn duplicate_lines(&mut self, lines: &LineRange) {
+        let (start, end) = match self.clamp_lines(lines) {
+            Some(lines) => lines,
+            None => return,
+        };
+        let content = self.lines_between(start, end);
+        let position = Position {
+            line: end - 1,
+            offset: self.line_length(end - 1).unwrap_or(0),
+        };
+        let cursor = self.cursor.position;
+
+        self.in_operation_group(|buffer| {
+            buffer.apply_operation(Box::new(Insert::new(
+                format!("\n{}", content.join("\n")),
+                position,
+            )))
+        });
+
+        self.move_cursor_with_lines(cursor, start, end, (end - start) as isize);
+    }
+
+    /// Swaps the lines with the one above them, moving the cursor along with
+    /// them. Returns false if the lines are already at the top of the buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::{LineRange, Position};
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("scribe\nlibrary\neditor");
+    /// buffer.cursor.move_to(Position{ line: 2, offset: 1 });
+    ///
+    /// assert!(buffer.move_lines_up(&LineRange::new(1, 3)));
+    /// assert_eq!(buffer.data(), "library\neditor\nscribe");
+    /// assert_eq!(*buffer.cursor, Position{ line: 1, offset: 1 });
+    /// ```
+    pub fn move_lines_up(&mut self, lines: &LineRange) -> bool {
+        let (start, end) = match self.clamp_lines(lines) {
+            Some((start, end)) if start > 0 => (start, end),
+            _ => return false,
+        };
+        let cursor = self.cursor.position;
+        let mut content = self.lines_between(start - 1, end);
+        content.rotate_left(1);
+
+        self.in_operation_group(|buffer| buffer.replace_lines(start - 1, end, &content));
+
+        self.move_cursor_with_lines(cursor, start, end, -1);
+        true
+    }
+
+    /// Swaps the lines with the one below them, moving the cursor along with
+    /// them. Returns false if the lines are already at the bottom of the buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::{LineRange, Position};
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("scribe\nlibrary\neditor");
+    ///
+    /// assert!(buffer.move_lines_down(&LineRange::new(0, 1)));
+    /// assert_eq!(buffer.data(), "library\nscribe\neditor");
+    /// assert_eq!(*buffer.cursor, Position{ line: 1, offset: 0 });
+    /// ```
+    pub fn move_lines_down(&mut self, lines: &LineRange) -> bool {
+        let (start, end) = match self.clamp_lines(lines) {
+            Some((start, end)) if end < self.line_count() => (start, end),
+            _ => return false,
+        };
+        let cursor = self.cursor.position;
+        let mut content = self.lines_between(start, end + 1);
+        content.rotate_right(1);
+
+        self.in_operation_group(|buffer| buffer.replace_lines(start, end + 1, &content));
+
+        self.move_cursor_with_lines(cursor, start, end, 1);
+        true
+    }
+
+    /// Joins the lines into one, replacing each line break and the following
+    /// line's leading whitespace with a single space. A range covering a
+    /// single line joins it with the line below. The cursor is moved to the
+    /// last join point.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::{LineRange, Position};
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("scribe\n    library\neditor");
+    /// buffer.join_lines(&LineRange::new(0, 1));
+    ///
+    /// assert_eq!(buffer.data(), "scribe library\neditor");
+    /// assert_eq!(*buffer.cursor, Position{ line: 0, offset: 6 });
+    /// ```
+    pub fn join_lines(&mut self, lines: &LineRange) {
+        let end = lines.end().max(lines.start() + 2);
+        let (start, end) = match self.clamp_lines(&LineRange::new(lines.start(), end)) {
+            Some((start, end)) if end - start > 1 => (start, end),
+            _ => return,
+        };
+
+        let mut content = self.lines_between(start, end).into_iter();
+        let mut joined = content.next().unwrap_or_default();
+        let mut join_point = 0;
+        for line in content {
+            join_point = joined.graphemes(true).count();
+
+            let line = line.trim_start();
+            if line.is_empty() {
+                continue;
+            }
+            if !joined.is_empty() && !joined.ends_with(char::is_whitespace) {
+                joined.push(' ');
+            }
+            joined.push_str(line);
+        }
+
+        self.in_operation_group(|buffer| buffer.replace_lines(start, end, &[joined]));
+
+        self.cursor.move_to(Position {
+            line: start,
+            offset: join_point,
+        });
+    }
+
+    /// Removes the lines from the buffer, moving the cursor to the start of
+    /// the line that follows them (or the last line, if there isn't one).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::{LineRange, Position};
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("scribe\nlibrary\neditor");
+    /// buffer.delete_lines(&LineRange::new(0, 2));
+    ///
+    /// assert_eq!(buffer.data(), "editor");
+    /// assert_eq!(*buffer.cursor, Position{ line: 0, offset: 0 });
+    /// ```
+    pub fn delete_lines(&mut self, lines: &LineRange) {
+        let (range, _) = match self.line_extent(lines) {
+            Some(extent) => extent,
+            None => return,
+        };
+
+        self.in_operation_group(|buffer| buffer.apply_operation(Box::new(Delete::new(range))));
+
+        self.cursor.move_to(Position {
+            line: lines.start().min(self.line_count() - 1),
+            offset: 0,
+        });
+    }
+
+    /// Sorts the lines, using a stable sort. The cursor remains at
+    /// its position in the buffer, rather than following its line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::{LineRange, SortOptions};
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("10 scribe\n9 library\n100 editor");
+    /// buffer.sort_lines(&LineRange::new(0, 3), SortOptions::default());
+    /// assert_eq!(buffer.data(), "10 scribe\n100 editor\n9 library");
+    ///
+    /// buffer.sort_lines(
+    ///     &LineRange::new(0, 3),
+    ///     SortOptions{ numeric: true, ..Default::default() }
+    /// );
+    /// assert_eq!(buffer.data(), "9 library\n10 scribe\n100 editor");
+    /// ```
+    pub fn sort_lines(&mut self, lines: &LineRange, options: SortOptions) {
+        self.transform_lines(lines, |content| {
+            content.sort_by(|a, b| compare_lines(a, b, options));
+        });
+    }
+
+    /// Reverses the order of the lines. The cursor remains at
+    /// its position in the buffer, rather than following its line.
+    pub fn reverse_lines(&mut self, lines: &LineRange) {
+        self.transform_lines(lines, |content| content.reverse());
+    }
+
+    /// Removes lines that duplicate an earlier line in the range, retaining
+    /// the first occurrence of each. The cursor remains at its position in
+    /// the buffer, rather than following its line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::LineRange;
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("scribe\nlibrary\nscribe\neditor");
+    /// buffer.remove_duplicate_lines(&LineRange::new(0, 4));
+    ///
+    /// assert_eq!(buffer.data(), "scribe\nlibrary\neditor");
+    /// ```
+    pub fn remove_duplicate_lines(&mut self, lines: &LineRange) {
+        self.transform_lines(lines, |content| {
+            let mut seen = HashSet::new();
+            content.retain(|line| seen.insert(line.clone()));
+        });
+    }
+
+    // The range covering complete lines, along with their content. The content
+    // always ends with a newline; if the lines extend to the end of the buffer,
+    // the range includes the preceding newline instead, so that deleting it
+    // doesn't leave an empty line behind.
+    pub(crate) fn line_extent(&self, lines: &LineRange) -> Option<(Range, String)> {
+        let line_count = self.line_count();
+        let start = Position {
+            line: lines.start(),
+            offset: 0,
+        };
+        if lines.start() >= line_count || lines.start() == lines.end() {
+            return None;
+        }
+
+        if lines.end() < line_count {
+            let range = Range::new(
+                start,
+                Position {
+                    line: lines.end(),
+                    offset: 0,
+                },
+            );
+
+            return self.read(&range).map(|content| (range, content));
+        }
+
+        let end = self.end_position();
+        let mut content = self.read(&Range::new(start, end))?;
+        content.push('\n');
+
+        let range_start = match lines.start().checked_sub(1) {
+            Some(line) => Position {
+                line,
+                offset: self.line_length(line)?,
+            },
+            None => start,
+        };
+
+        Some((Range::new(range_start, end), content))
+    }
+
+    // Rewrites the lines using the provided function, as a single operation,
+    // retaining the cursor position (or failing that, its line) if possible.
+    pub(crate) fn transform_lines<F: FnOnce(&mut Vec<String>)>(
+        &mut self,
+        lines: &LineRange,
+        transform: F,
+    ) {
+        let (start, end) = match self.clamp_lines(lines) {
+            Some(lines) => lines,
+            None => return,
+        };
+        let cursor = self.cursor.position;
+        let mut content = self.lines_between(start, end);
+        transform(&mut content);
+
+        self.in_operation_group(|buffer| buffer.replace_lines(start, end, &content));
+
+        if !self.cursor.move_to(cursor) {
+            self.cursor.move_to(Position {
+                line: cursor.line.min(self.line_count() - 1),
+                offset: 0,
+            });
+        }
+    }
+
+    // Limits the end-exclusive line range to the buffer's lines,
+    // returning None if there are no lines left in it.
+    fn clamp_lines(&self, lines: &LineRange) -> Option<(usize, usize)> {
+        let end = lines.end().min(self.line_count());
+        if lines.start() < end {
+            Some((lines.start(), end))
+        } else {
+            None
+        }
+    }
+
+    // The content of the lines, excluding their newlines.
+    fn lines_between(&self, start: usize, end: usize) -> Vec<String> {
+        self.data()
+            .split('\n')
+            .skip(start)
+            .take(end - start)
+            .map(String::from)
+            .collect()
+    }
+
+    // Replaces the content of the lines (excluding the last line's newline)
+    // with the provided lines, skipping the edit if nothing would change.
+    fn replace_lines(&mut self, start: usize, end: usize, content: &[String]) {
+        let content = content.join("\n");
+        if content == self.lines_between(start, end).join("\n") {
+            return;
+        }
+
+        let start = Position {
+            line: start,
+            offset: 0,
+        };
+        let range = Range::new(
+            start,
+            Position {
+                line: end - 1,
+                offset: self.line_length(end - 1).unwrap_or(0),
+            },
+        );
+
+        self.apply_operation(Box::new(Delete::new(range)));
+        if !content.is_empty() {
+            self.apply_operation(Box::new(Insert::new(content, start)));
+        }
+    }
+
+    // Moves the cursor by the specified number of lines if it was within the
+    // lines that moved, or to the start of the moved lines, otherwise.
+    fn move_cursor_with_lines(&mut self, cursor: Position, start: usize, end: usize, lines: isize) {
+        let position = if cursor.line >= start && cursor.line < end {
+            Position {
+                line: cursor.line.saturating_add_signed(lines),
+                offset: cursor.offset,
+            }
+        } else {
+            Position {
+                line: start.saturating_add_signed(lines),
+                offset: 0,
+            }
+        };
+
+        self.cursor.move_to(position);
+    }
+}
+
+fn compare_lines(a: &str, b: &str, options: SortOptions) -> Ordering {
+    let numeric_order = if options.numeric {
+        match (leading_number(a), leading_number(b)) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    } else {
+        Ordering::Equal
+    };
+
+    numeric_order.then_with(|| {
+        if options.case_insensitive {
+            a.to_lowercase().cmp(&b.to_lowercase())
+        } else {
+            a.cmp(b)
+        }
+    })
+}
+
+// Parses the number at the start of the line (ignoring leading whitespace),
+// allowing for a sign and fractional component.
+fn leading_number(line: &str) -> Option<f64> {
+    let line = line.trim_start();
+    let mut length = 0;
+    let mut seen_point = false;
+    for (index, character) in line.char_indices() {
+        match character {
+            '-' | '+' if index == 0 => (),
+            '.' if !seen_point => seen_point = true,
+            '0'..='9' => (),
+            _ => break,
+        }
+        length = index + character.len_utf8();
+    }
+
+    line[..length].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::{Buffer, LineRange, Position, SortOptions};
+
+    #[test]
+    fn duplicate_lines_is_undone_as_a_single_operation() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary");
+        buffer.duplicate_lines(&LineRange::new(0, 2));
+        assert_eq!(buffer.data(), "scribe\nlibrary\nscribe\nlibrary");
+
+        buffer.undo();
+        assert_eq!(buffer.data(), "scribe\nlibrary");
+    }
+
+    #[test]
+    fn duplicate_lines_moves_cursor_outside_of_range_to_copy() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary\neditor");
+        buffer.cursor.move_to(Position { line: 2, offset: 3 });
+        buffer.duplicate_lines(&LineRange::new(0, 2));
+
+        assert_eq!(*buffer.cursor, Position { line: 2, offset: 0 });
+    }
+
+    #[test]
+    fn move_lines_up_returns_false_at_top_of_buffer() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary");
+
+        assert!(!buffer.move_lines_up(&LineRange::new(0, 1)));
+        assert_eq!(buffer.data(), "scribe\nlibrary");
+    }
+
+    #[test]
+    fn move_lines_down_returns_false_at_bottom_of_buffer() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary");
+
+        assert!(!buffer.move_lines_down(&LineRange::new(1, 2)));
+        assert!(!buffer.move_lines_down(&LineRange::new(0, 5)));
+        assert_eq!(buffer.data(), "scribe\nlibrary");
+    }
+
+    #[test]
+    fn move_lines_down_is_undone_as_a_single_operation() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary\neditor");
+        buffer.move_lines_down(&LineRange::new(0, 2));
+        assert_eq!(buffer.data(), "editor\nscribe\nlibrary");
+
+        buffer.undo();
+        assert_eq!(buffer.data(), "scribe\nlibrary\neditor");
+    }
+
+    #[test]
+    fn join_lines_skips_empty_lines() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\n\n  library \neditor");
+        buffer.join_lines(&LineRange::new(0, 4));
+
+        assert_eq!(buffer.data(), "scribe library editor");
+        assert_eq!(
+            *buffer.cursor,
+            Position {
+                line: 0,
+                offset: 15
+            }
+        );
+    }
+
+    #[test]
+    fn join_lines_does_nothing_on_last_line() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary");
+        buffer.join_lines(&LineRange::new(1, 2));
+
+        assert_eq!(buffer.data(), "scribe\nlibrary");
+    }
+
+    #[test]
+    fn delete_lines_removes_preceding_newline_for_trailing_lines() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary\neditor");
+        buffer.delete_lines(&LineRange::new(1, 3));
+
+        assert_eq!(buffer.data(), "scribe");
+        assert_eq!(*buffer.cursor, Position { line: 0, offset: 0 });
+    }
+
+    #[test]
+    fn sort_lines_can_ignore_case() {
+        let mut buffer = Buffer::new();
+        buffer.insert("b\nC\na");
+        buffer.sort_lines(&LineRange::new(0, 3), SortOptions::default());
+        assert_eq!(buffer.data(), "C\na\nb");
+
+        buffer.sort_lines(
+            &LineRange::new(0, 3),
+            SortOptions {
+                case_insensitive: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(buffer.data(), "a\nb\nC");
+    }
+
+    #[test]
+    fn sort_lines_numerically_handles_signs_and_fractions() {
+        let mut buffer = Buffer::new();
+        buffer.insert("1.5\n-2\nscribe\n1\n");
+        buffer.sort_lines(
+            &LineRange::new(0, 4),
+            SortOptions {
+                numeric: true,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(buffer.data(), "scribe\n-2\n1\n1.5\n");
+    }
+
+    #[test]
+    fn reverse_lines_retains_cursor_position() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary\neditor");
+        buffer.cursor.move_to(Position { line: 1, offset: 2 });
+        buffer.reverse_lines(&LineRange::new(0, 3));
+
+        assert_eq!(buffer.data(), "editor\nlibrary\nscribe");
+        assert_eq!(*buffer.cursor, Position { line: 1, offset: 2 });
+
+        buffer.undo();
+        assert_eq!(buffer.data(), "scribe\nlibrary\neditor");
+    }
+
+    #[test]
+    fn remove_duplicate_lines_does_nothing_without_duplicates() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary");
+        buffer.remove_duplicate_lines(&LineRange::new(0, 2));
+
+        assert_eq!(buffer.data(), "scribe\nlibrary");
+        buffer.undo();
+        assert_eq!(buffer.data(), "");
+    }
+}
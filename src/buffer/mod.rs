@@ -4,24 +4,45 @@
 pub use self::cursor::Cursor;
 pub use self::distance::Distance;
 pub use self::gap_buffer::GapBuffer;
+pub use self::indentation::{IndentStyle, Indentation};
+pub use self::keyboard_macro::{Macro, MacroStep, Motion};
+pub use self::line_commands::SortOptions;
 pub use self::line_range::LineRange;
+pub use self::operation::history::{CoalescingPolicy, HistoryLimits};
+pub use self::operation::{
+    Delete, EditKind, GraphemeEdit, Insert, Operation, OperationGroup, Replace,
+};
 pub use self::position::Position;
 pub use self::range::Range;
-pub use self::token::{Lexeme, Token, TokenSet};
+pub use self::save_options::{LineEnding, SaveOptions, SaveTransform};
+pub use self::text_object::{Extent, TextObject};
+pub use self::text_transform::Case;
+pub use self::token::{Lexeme, Token, TokenError, TokenSet};
 pub use syntect::parsing::{Scope, ScopeStack};
 
 // Child modules
+mod bracket;
+mod comment;
 mod cursor;
+mod diff;
 mod distance;
+mod filter;
 mod gap_buffer;
+mod indentation;
+mod keyboard_macro;
+mod line_commands;
 mod line_range;
 mod operation;
 mod position;
 mod range;
+mod save_options;
+mod scope;
+mod text_object;
+mod text_transform;
 mod token;
 
 use self::operation::history::History;
-use self::operation::{Operation, OperationGroup};
+use self::token::ParseCache;
 use crate::errors::*;
 use std::cell::RefCell;
 use std::default::Default;
@@ -30,8 +51,8 @@ use std::io;
 use std::io::Write;
 use std::ops::Fn;
 use std::path::{Path, PathBuf};
-use std::rc::Rc;
-use syntect::parsing::SyntaxReference;
+use std::rc::{Rc, Weak};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
 
 /// A feature-rich wrapper around an underlying gap buffer.
 ///
@@ -43,7 +64,8 @@ use syntect::parsing::SyntaxReference;
 ///
 /// If the buffer is configured with a `change_callback`, it will be called with
 /// a position whenever the buffer is modified; it's particularly useful for
-/// cache invalidation.
+/// cache invalidation. Similarly, any `save_transforms` are run against its
+/// content when it's saved (e.g. to format it).
 pub struct Buffer {
     pub id: Option<usize>,
     data: Rc<RefCell<GapBuffer>>,
@@ -51,8 +73,15 @@ pub struct Buffer {
     pub cursor: Cursor,
     history: History,
     operation_group: Option<OperationGroup>,
+    operation_group_cursor: Position,
+    macro_recording: Option<Macro>,
     pub syntax_definition: Option<SyntaxReference>,
+    pub syntax_locked: bool,
+    pub indentation: Indentation,
+    pub save_options: SaveOptions,
+    pub save_transforms: Vec<SaveTransform>,
     pub change_callback: Option<Box<dyn Fn(Position)>>,
+    parse_cache: Rc<RefCell<ParseCache>>,
 }
 
 impl Default for Buffer {
@@ -69,8 +98,15 @@ impl Default for Buffer {
             cursor,
             history: History::new(),
             operation_group: None,
+            operation_group_cursor: Position::new(),
+            macro_recording: None,
             syntax_definition: None,
+            syntax_locked: false,
+            indentation: Indentation::default(),
+            save_options: SaveOptions::default(),
+            save_transforms: Vec::new(),
             change_callback: None,
+            parse_cache: Default::default(),
         }
     }
 }
@@ -112,7 +148,8 @@ impl Buffer {
     /// ```
     pub fn from_file(path: &Path) -> io::Result<Buffer> {
         // Try to open and read the file, returning any errors encountered.
-        let content = fs::read_to_string(path)?;
+        let (content, byte_order_mark, line_ending) = decode(fs::read_to_string(path)?);
+        let indentation = Indentation::detect(&content).unwrap_or_default();
 
         let data = Rc::new(RefCell::new(GapBuffer::new(content)));
         let cursor = Cursor::new(data.clone(), Position { line: 0, offset: 0 });
@@ -125,8 +162,19 @@ impl Buffer {
             cursor,
             history: History::new(),
             operation_group: None,
+            operation_group_cursor: Position::new(),
+            macro_recording: None,
             syntax_definition: None,
+            syntax_locked: false,
+            indentation,
+            save_options: SaveOptions {
+                byte_order_mark,
+                line_ending,
+                ..Default::default()
+            },
+            save_transforms: Vec::new(),
             change_callback: None,
+            parse_cache: Default::default(),
         };
 
         // We mark the history at points where the
@@ -151,7 +199,23 @@ impl Buffer {
         self.data.borrow().to_string()
     }
 
-    /// Writes the contents of the buffer to its path.
+    // Tokenizes the buffer using its syntax definition, or plain text if it
+    // doesn't have one, re-using parse results for lines that haven't changed.
+    pub(crate) fn tokens<'a>(&'a self, syntax_set: &'a SyntaxSet) -> TokenSet<'a> {
+        let syntax_definition = self
+            .syntax_definition
+            .as_ref()
+            .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+
+        TokenSet::new(self.data(), syntax_definition, syntax_set)
+            .with_cache(self.parse_cache.clone())
+    }
+
+    /// Writes the contents of the buffer to its path. The buffer's
+    /// `save_transforms` are run first (in order), followed by its
+    /// `save_options`, with any resulting changes to its content recorded as
+    /// a single undoable operation, preceding the point at which the buffer
+    /// is considered unmodified.
     ///
     /// # Examples
     ///
@@ -185,8 +249,8 @@ impl Buffer {
             File::create(PathBuf::new())?
         };
 
-        // We use to_string here because we don't want to write the gap contents.
-        file.write_all(self.data().to_string().as_bytes())?;
+        self.apply_save_options();
+        file.write_all(&self.encoded_data())?;
 
         // We mark the history at points where the
         // buffer is in sync with its file equivalent.
@@ -215,7 +279,8 @@ impl Buffer {
         })
     }
 
-    /// Reverses the last modification to the buffer.
+    /// Reverses the last modification to the buffer, restoring the cursor
+    /// to the position it was at before the modification was made.
     ///
     /// # Examples
     ///
@@ -233,8 +298,10 @@ impl Buffer {
     /// assert_eq!("scribe library", buffer.data());
     ///
     /// // Undo the second operation.
+    /// buffer.cursor.move_to(Position{ line: 0, offset: 14});
     /// buffer.undo();
     /// assert_eq!("scribe", buffer.data());
+    /// assert_eq!(*buffer.cursor, Position{ line: 0, offset: 6});
     ///
     /// // Undo the first operation.
     /// buffer.undo();
@@ -243,24 +310,27 @@ impl Buffer {
     pub fn undo(&mut self) {
         // Look for an operation to undo. First, check if there's an open, non-empty
         // operation group. If not, try taking the last operation from the buffer history.
-        let operation: Option<Box<dyn Operation>> = match self.operation_group.take() {
+        let operation: Option<(Box<dyn Operation>, Position)> = match self.operation_group.take() {
             Some(group) => {
                 if group.is_empty() {
                     self.history.previous()
                 } else {
-                    Some(Box::new(group))
+                    Some((Box::new(group), self.operation_group_cursor))
                 }
             }
             None => self.history.previous(),
         };
 
-        // If we found an eligible operation, reverse it.
-        if let Some(mut op) = operation {
+        // If we found an eligible operation, reverse
+        // it and restore the cursor's prior position.
+        if let Some((mut op, position)) = operation {
             op.reverse(self);
+            self.cursor.move_to(position);
         }
     }
 
-    /// Re-applies the last undone modification to the buffer.
+    /// Re-applies the last undone modification to the buffer, restoring the
+    /// cursor to the position it was at once the modification was made.
     ///
     /// # Examples
     ///
@@ -278,11 +348,158 @@ impl Buffer {
     /// ```
     pub fn redo(&mut self) {
         // Look for an operation to apply.
-        if let Some(mut op) = self.history.next() {
+        if let Some((mut op, position)) = self.history.next() {
             op.run(self);
+            self.cursor.move_to(position);
         }
     }
 
+    /// Runs a reversible operation against the buffer, storing it in the
+    /// buffer's history (or open operation group) so that it can be undone.
+    /// This is how built-in operations like `insert` and `delete` are applied,
+    /// and can be used to apply custom `Operation` implementations with the
+    /// same undo/redo and modification tracking semantics.
+    ///
+    /// Operations applied this way can't be replayed relative to the cursor,
+    /// so they end any in-progress macro recording, discarding it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::{Insert, Position};
+    ///
+    /// let mut buffer = Buffer::new();
+    /// let position = Position{ line: 0, offset: 0 };
+    /// buffer.apply_operation(Box::new(Insert::new("scribe".to_string(), position)));
+    /// assert_eq!(buffer.data(), "scribe");
+    ///
+    /// buffer.undo();
+    /// assert_eq!(buffer.data(), "");
+    /// ```
+    pub fn apply_operation(&mut self, operation: Box<dyn Operation>) {
+        self.macro_recording = None;
+        self.run_operation(operation);
+    }
+
+    // Runs and stores an operation without affecting macro recording, for
+    // edits that record their own macro steps (e.g. `insert` and `delete`).
+    fn run_operation(&mut self, mut operation: Box<dyn Operation>) {
+        let cursor = self.cursor.position;
+        operation.run(self);
+        self.record_operation(operation, cursor);
+    }
+
+    // Stores an operation that has already been run in the open operation
+    // group, if one exists, or the history, so that it can be undone. The
+    // cursor positions from before and after the operation was run are stored
+    // alongside it, so that they can be restored when it's undone and redone.
+    // Groups record the cursor from when they're ended, instead.
+    fn record_operation(&mut self, operation: Box<dyn Operation>, cursor: Position) {
+        match self.operation_group {
+            Some(ref mut group) => {
+                if group.is_empty() {
+                    self.operation_group_cursor = cursor;
+                }
+
+                group.add(operation)
+            }
+            None => self.history.add(operation, cursor, self.cursor.position),
+        };
+    }
+
+    /// Returns the limits currently applied to the buffer's undo/redo history.
+    pub fn history_limits(&self) -> HistoryLimits {
+        self.history.limits()
+    }
+
+    /// Bounds the buffer's undo/redo history by operation count and/or retained
+    /// content size. Once exceeded, the oldest history entries are discarded;
+    /// if the entry at which the buffer was last saved is among them, the
+    /// buffer will remain flagged as modified until it is saved again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::{HistoryLimits, Position};
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.set_history_limits(HistoryLimits {
+    ///     operations: Some(1),
+    ///     size: None,
+    /// });
+    ///
+    /// buffer.insert("scribe");
+    /// buffer.cursor.move_to(Position{ line: 0, offset: 6 });
+    /// buffer.insert(" library");
+    ///
+    /// // Only the most recent operation is retained.
+    /// buffer.undo();
+    /// buffer.undo();
+    /// assert_eq!(buffer.data(), "scribe");
+    /// ```
+    pub fn set_history_limits(&mut self, limits: HistoryLimits) {
+        self.history.set_limits(limits);
+    }
+
+    /// The approximate number of content bytes retained by the buffer's
+    /// undo/redo history, including any open operation group.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("scribe");
+    /// assert_eq!(buffer.history_size(), 6);
+    /// ```
+    pub fn history_size(&self) -> usize {
+        let group_size = self
+            .operation_group
+            .as_ref()
+            .map(|group| group.size())
+            .unwrap_or(0);
+
+        self.history.size() + group_size
+    }
+
+    /// Returns the policy used to coalesce consecutive edits into single
+    /// undo/redo steps, if one has been configured.
+    pub fn history_coalescing(&self) -> Option<CoalescingPolicy> {
+        self.history.coalescing()
+    }
+
+    /// Configures the buffer to merge adjacent single-grapheme inserts and
+    /// deletes into single undo/redo steps, as described by `CoalescingPolicy`.
+    /// Coalescing is disabled by default; passing `None` disables it again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::{CoalescingPolicy, Position};
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.set_history_coalescing(Some(CoalescingPolicy::default()));
+    ///
+    /// // Type out two words, one character at a time.
+    /// for (offset, character) in "scribe library".chars().enumerate() {
+    ///     buffer.cursor.move_to(Position{ line: 0, offset });
+    ///     buffer.insert(character);
+    /// }
+    ///
+    /// // Each word is undone as a whole.
+    /// buffer.undo();
+    /// assert_eq!(buffer.data(), "scribe");
+    /// buffer.undo();
+    /// assert_eq!(buffer.data(), "");
+    /// ```
+    pub fn set_history_coalescing(&mut self, policy: Option<CoalescingPolicy>) {
+        self.history.set_coalescing(policy);
+    }
+
     /// Tries to read the specified range from the buffer.
     ///
     /// # Examples
@@ -389,6 +606,23 @@ impl Buffer {
         self.data().chars().filter(|&c| c == '\n').count() + 1
     }
 
+    // The length of the specified line, in grapheme clusters,
+    // excluding its newline. Returns None if the line doesn't exist.
+    pub(crate) fn line_length(&self, line: usize) -> Option<usize> {
+        self.data.borrow().line_length(line)
+    }
+
+    // The position immediately following the buffer's last character.
+    pub(crate) fn end_position(&self) -> Position {
+        self.data.borrow().end_position()
+    }
+
+    // Identifies the buffer for as long as it exists. Unlike `id`, which is
+    // only assigned to buffers in a workspace, this is unique to every buffer.
+    pub(crate) fn identity(&self) -> Weak<RefCell<GapBuffer>> {
+        Rc::downgrade(&self.data)
+    }
+
     /// Reloads the buffer from disk, discarding any in-memory modifications and
     /// history. This method will make best efforts to retain the full cursor
     /// position, then cursor line, and will ultimately fall back to resetting
@@ -413,9 +647,11 @@ impl Buffer {
     pub fn reload(&mut self) -> Result<()> {
         // Load content from disk.
         let path = self.path.as_ref().ok_or(ErrorKind::MissingPath)?;
-        let content = fs::read_to_string(path)?;
+        let (content, byte_order_mark, line_ending) = decode(fs::read_to_string(path)?);
 
         self.replace(content);
+        self.save_options.byte_order_mark = byte_order_mark;
+        self.save_options.line_ending = line_ending;
 
         // We mark the history at points where the
         // buffer is in sync with its file equivalent.
@@ -456,13 +692,34 @@ impl Buffer {
     }
 }
 
+// Strips any byte order mark from a file's content, and converts its line
+// terminators to newlines if they're consistent (mixed terminators are left
+// as-is). Returns whether a byte order mark was present, along with the
+// converted terminator, so that both can be restored on save.
+fn decode(mut content: String) -> (String, bool, Option<LineEnding>) {
+    let byte_order_mark = content.starts_with('\u{feff}');
+    if byte_order_mark {
+        content.remove(0);
+    }
+
+    let line_ending = LineEnding::detect(&content);
+    match line_ending {
+        Some(LineEnding::CrLf) => content = content.replace("\r\n", "\n"),
+        Some(LineEnding::Cr) => content = content.replace('\r', "\n"),
+        _ => (),
+    }
+
+    (content, byte_order_mark, line_ending)
+}
+
 #[cfg(test)]
 mod tests {
     extern crate syntect;
-    use crate::buffer::{Buffer, Position};
+    use crate::buffer::{Buffer, Insert, Position, Range, TokenSet};
     use std::cell::RefCell;
     use std::path::Path;
     use std::rc::Rc;
+    use std::{env, fs};
     use syntect::parsing::SyntaxSet;
 
     #[test]
@@ -580,6 +837,35 @@ mod tests {
         assert!(buffer.history.previous().is_some());
     }
 
+    #[test]
+    fn reload_strips_byte_order_marks_and_line_endings() {
+        let file_path = env::temp_dir().join("scribe_reload_byte_order_mark");
+        fs::write(&file_path, "scribe\n").unwrap();
+        let mut buffer = Buffer::from_file(&file_path).unwrap();
+        fs::write(&file_path, "\u{feff}scribe\r\n").unwrap();
+        buffer.reload().unwrap();
+        assert_eq!(buffer.data(), "scribe\n");
+
+        buffer.save().unwrap();
+        let data = fs::read_to_string(&file_path).unwrap();
+        fs::remove_file(&file_path).unwrap();
+        assert_eq!(data, "\u{feff}scribe\r\n");
+    }
+
+    #[test]
+    fn from_file_retains_mixed_line_endings() {
+        let file_path = env::temp_dir().join("scribe_mixed_line_endings");
+        fs::write(&file_path, "scribe\r\nlibrary\neditor\r\n").unwrap();
+        let mut buffer = Buffer::from_file(&file_path).unwrap();
+        assert_eq!(buffer.data(), "scribe\r\nlibrary\neditor\r\n");
+        assert_eq!(buffer.save_options.line_ending, None);
+
+        buffer.save().unwrap();
+        let data = fs::read_to_string(&file_path).unwrap();
+        fs::remove_file(&file_path).unwrap();
+        assert_eq!(data, "scribe\r\nlibrary\neditor\r\n");
+    }
+
     #[test]
     fn delete_joins_lines_when_invoked_at_end_of_line() {
         let mut buffer = Buffer::new();
@@ -697,6 +983,85 @@ mod tests {
         assert_eq!(buffer.data(), "");
     }
 
+    #[test]
+    fn undo_and_redo_restore_cursor_positions() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary\n\n");
+        buffer.cursor.move_to(Position { line: 1, offset: 1 });
+        buffer.start_operation_group();
+        buffer.insert("a");
+        buffer.cursor.move_right();
+        buffer.end_operation_group();
+        buffer.cursor.move_to(Position { line: 3, offset: 0 });
+
+        // Undo restores the position from before the edit.
+        buffer.undo();
+        assert_eq!(*buffer.cursor, Position { line: 1, offset: 1 });
+
+        // Redo restores the position from after the edit,
+        // rather than the one from which it was undone.
+        buffer.cursor.move_to(Position { line: 3, offset: 0 });
+        buffer.redo();
+        assert_eq!(*buffer.cursor, Position { line: 1, offset: 2 });
+
+        // Undoing and redoing again restores the same positions.
+        buffer.undo();
+        assert_eq!(*buffer.cursor, Position { line: 1, offset: 1 });
+        buffer.redo();
+        assert_eq!(*buffer.cursor, Position { line: 1, offset: 2 });
+    }
+
+    #[test]
+    fn undoing_an_operation_group_restores_the_cursor_position_before_the_group() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe");
+        buffer.cursor.move_to(Position { line: 0, offset: 6 });
+
+        buffer.start_operation_group();
+        buffer.insert(" library");
+        buffer.cursor.move_to(Position { line: 0, offset: 0 });
+        buffer.insert("the ");
+        buffer.end_operation_group();
+        buffer.cursor.move_to(Position { line: 0, offset: 4 });
+
+        buffer.undo();
+        assert_eq!(buffer.data(), "scribe");
+        assert_eq!(*buffer.cursor, Position { line: 0, offset: 6 });
+    }
+
+    #[test]
+    fn undoing_a_replacement_restores_the_cursor_position() {
+        let mut buffer = Buffer::new();
+        buffer.insert("amp\neditor");
+        buffer.cursor.move_to(Position { line: 1, offset: 3 });
+        buffer.replace("scribe");
+        assert_eq!(*buffer.cursor, Position { line: 0, offset: 0 });
+
+        buffer.undo();
+        assert_eq!(*buffer.cursor, Position { line: 1, offset: 3 });
+    }
+
+    #[test]
+    fn apply_operation_tracks_modified_state_and_calls_change_callback() {
+        let file_path = Path::new("tests/sample/file");
+        let mut buffer = Buffer::from_file(file_path).unwrap();
+
+        // Create a position that we'll share with the callback.
+        let tracked_position = Rc::new(RefCell::new(Position::new()));
+        let callback_position = tracked_position.clone();
+        buffer.change_callback = Some(Box::new(move |change_position| {
+            *callback_position.borrow_mut() = change_position
+        }));
+
+        let position = Position { line: 1, offset: 0 };
+        buffer.apply_operation(Box::new(Insert::new("scribe".to_string(), position)));
+        assert!(buffer.modified());
+        assert_eq!(*tracked_position.borrow(), position);
+
+        buffer.undo();
+        assert!(!buffer.modified());
+    }
+
     #[test]
     fn search_returns_empty_set_when_there_are_no_matches() {
         let mut buffer = Buffer::new();
@@ -723,4 +1088,41 @@ mod tests {
         // Use a matching term.
         assert!(buffer.search("scribé").len() > 0);
     }
+
+    #[test]
+    fn tokens_reflect_edits_made_after_caching() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert("fn main() {\n    scribe();\n}\n".repeat(20));
+        buffer.tokens(&syntax_set).iter().unwrap().for_each(drop);
+
+        // Open a block comment, add and remove lines, and undo some of it.
+        buffer.insert("/* ");
+        buffer.cursor.move_to(Position {
+            line: 30,
+            offset: 0,
+        });
+        buffer.insert("*/\nlet x = \"\n\";\n");
+        buffer.delete_range(Range::new(
+            Position {
+                line: 40,
+                offset: 0,
+            },
+            Position {
+                line: 45,
+                offset: 2,
+            },
+        ));
+        buffer.tokens(&syntax_set).iter().unwrap().for_each(drop);
+        buffer.undo();
+
+        let tokens = buffer.tokens(&syntax_set);
+        let uncached = TokenSet::new(
+            buffer.data(),
+            buffer.syntax_definition.as_ref().unwrap(),
+            &syntax_set,
+        );
+        assert!(tokens.iter().unwrap().eq(uncached.iter().unwrap()));
+    }
 }
@@ -1,6 +1,7 @@
-use crate::buffer::operation::Operation;
-use crate::buffer::{Buffer, Position, Range};
+use crate::buffer::operation::{EditKind, GraphemeEdit, Operation};
+use crate::buffer::{Buffer, MacroStep, Position, Range};
 use std::clone::Clone;
+use unicode_segmentation::UnicodeSegmentation;
 
 /// A reversible buffer delete operation.
 ///
@@ -21,6 +22,18 @@ impl Operation for Delete {
         // Fetch and store the content we're about to delete.
         self.content = buffer.data.borrow().read(&self.range);
 
+        // Deletes with an out of bounds end are clamped, so we can't
+        // reliably determine which lines they remove from parsing.
+        if self.content.is_some() {
+            buffer.parse_cache.borrow_mut().invalidate(
+                self.range.start().line,
+                self.range.end().line - self.range.start().line,
+                0,
+            );
+        } else if buffer.data.borrow().in_bounds(&self.range.start()) {
+            buffer.parse_cache.borrow_mut().clear();
+        }
+
         // Delete the data.
         buffer.data.borrow_mut().delete(&self.range);
 
@@ -36,6 +49,11 @@ impl Operation for Delete {
                 .data
                 .borrow_mut()
                 .insert(content, &self.range.start());
+            buffer.parse_cache.borrow_mut().invalidate(
+                self.range.start().line,
+                0,
+                content.matches('\n').count(),
+            );
 
             // Run the change callback, if present.
             if let Some(ref callback) = buffer.change_callback {
@@ -47,10 +65,27 @@ impl Operation for Delete {
     fn clone_operation(&self) -> Box<dyn Operation> {
         Box::new(self.clone())
     }
+
+    fn size(&self) -> usize {
+        self.content.as_ref().map(|c| c.len()).unwrap_or(0)
+    }
+
+    fn grapheme_edit(&self) -> Option<GraphemeEdit<'_>> {
+        let content = self.content.as_ref()?;
+        if content.graphemes(true).count() != 1 {
+            return None;
+        }
+
+        Some(GraphemeEdit {
+            kind: EditKind::Delete,
+            position: self.range.start(),
+            grapheme: content,
+        })
+    }
 }
 
 impl Delete {
-    /// Creates a new empty delete operation.
+    /// Creates a new delete operation.
     pub fn new(range: Range) -> Delete {
         Delete {
             content: None,
@@ -94,9 +129,11 @@ impl Buffer {
         // The range we're building is going to be consumed,
         // so create a clone of the cursor's current position.
         let start = self.cursor.position;
+        self.record_macro_step(MacroStep::Delete);
 
-        // Now that we've established the range, defer.
-        self.delete_range(Range::new(start, end));
+        // Now that we've established the range, delete it. This bypasses
+        // delete_range, which would end the macro recording.
+        self.run_operation(Box::new(Delete::new(Range::new(start, end))));
     }
 
     /// Removes a range of characters from the buffer.
@@ -121,16 +158,8 @@ impl Buffer {
     /// assert_eq!(buffer.data(), "scribe");
     /// ```
     pub fn delete_range(&mut self, range: Range) {
-        // Build and run a delete operation.
-        let mut op = Delete::new(range);
-        op.run(self);
-
-        // Store the operation in the history
-        // object so that it can be undone.
-        match self.operation_group {
-            Some(ref mut group) => group.add(Box::new(op)),
-            None => self.history.add(Box::new(op)),
-        };
+        // Build and run a delete operation, storing it in history.
+        self.apply_operation(Box::new(Delete::new(range)));
     }
 }
 
@@ -11,6 +11,7 @@ use crate::buffer::Buffer;
 /// any other operation. It's a simple grouping type; it relies on its constituent operations
 /// to handle all of their undo/redo implementation details. It exposes two methods on the
 /// buffer type to signal the start and end of a group.
+#[derive(Default)]
 pub struct OperationGroup {
     operations: Vec<Box<dyn Operation>>,
 }
@@ -41,14 +42,17 @@ impl Operation for OperationGroup {
                 .collect(),
         })
     }
+
+    /// The combined size of all of the group's individual operations.
+    fn size(&self) -> usize {
+        self.operations.iter().map(|o| o.size()).sum()
+    }
 }
 
 impl OperationGroup {
     /// Creates a new empty operation group.
     pub fn new() -> OperationGroup {
-        OperationGroup {
-            operations: Vec::new(),
-        }
+        Default::default()
     }
 
     /// Adds an operation to the group.
@@ -83,10 +87,32 @@ impl Buffer {
         // Push an open operation group on to the history stack, if one exists.
         if let Some(group) = self.operation_group.take() {
             if !group.is_empty() {
-                self.history.add(Box::new(group))
+                self.history.add(
+                    Box::new(group),
+                    self.operation_group_cursor,
+                    self.cursor.position,
+                )
             }
         }
     }
+
+    // Runs the function with an operation group open, so that its operations
+    // are undone/applied together. If the caller already has a group open,
+    // they're added to it instead, and it's left open for the caller to end.
+    pub(crate) fn in_operation_group<T, F>(&mut self, function: F) -> T
+    where
+        F: FnOnce(&mut Buffer) -> T,
+    {
+        if self.operation_group.is_some() {
+            return function(self);
+        }
+
+        self.start_operation_group();
+        let result = function(self);
+        self.end_operation_group();
+
+        result
+    }
 }
 
 #[cfg(test)]
@@ -94,7 +120,7 @@ mod tests {
     use super::OperationGroup;
     use crate::buffer::operation::insert::Insert;
     use crate::buffer::operation::Operation;
-    use crate::buffer::{Buffer, Position};
+    use crate::buffer::{Buffer, LineRange, Position};
 
     #[test]
     fn run_and_reverse_call_themselves_on_all_operations() {
@@ -139,4 +165,22 @@ mod tests {
         buffer.undo();
         assert_eq!(buffer.data(), "");
     }
+
+    #[test]
+    fn commands_run_within_an_open_operation_group_extend_it() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary");
+
+        // Run a command that groups its own operations between others,
+        // all of which should be undone together with the caller's group.
+        buffer.start_operation_group();
+        buffer.insert("amp ");
+        buffer.duplicate_lines(&LineRange::new(0, 1));
+        buffer.insert("editor ");
+        buffer.end_operation_group();
+        assert_eq!(buffer.data(), "amp scribe\neditor amp scribe\nlibrary");
+
+        buffer.undo();
+        assert_eq!(buffer.data(), "scribe\nlibrary");
+    }
 }
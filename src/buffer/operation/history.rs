@@ -1,84 +1,373 @@
-use crate::buffer::operation::Operation;
+use crate::buffer::operation::{EditKind, GraphemeEdit, Operation, OperationGroup};
+use crate::buffer::Position;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Constraints on the amount of history retained by a buffer.
+///
+/// When either limit is exceeded, the oldest operations are discarded until
+/// the history fits within both limits again. A `None` value leaves the
+/// corresponding dimension unbounded, which is the default.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HistoryLimits {
+    /// The maximum number of undo/redo steps to retain.
+    pub operations: Option<usize>,
+
+    /// The maximum number of content bytes retained across all steps.
+    pub size: Option<usize>,
+}
+
+/// Rules for merging consecutive single-grapheme edits into one history step.
+///
+/// Adjacent insertions (or deletions) are merged as they're added to the
+/// history, so that undoing reverses a run of typing rather than a single
+/// character. A new step is started when the edit kind changes, the edit isn't
+/// adjacent to the previous one (e.g. the cursor has jumped), the grapheme is
+/// whitespace, or the idle timeout has elapsed. Newlines are never merged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CoalescingPolicy {
+    /// The maximum delay between edits merged into the same step.
+    pub idle_timeout: Option<Duration>,
+}
+
+impl Default for CoalescingPolicy {
+    fn default() -> Self {
+        CoalescingPolicy {
+            idle_timeout: Some(Duration::from_secs(1)),
+        }
+    }
+}
 
 /// Tracks a series of operations.
 ///
 /// Represents a linear history that can be traversed backwards and forwards.
 /// Adding a new operation to the history will clear any previously reversed
 /// operations, which would otherwise have been eligible to be redone.
+///
+/// The history can be bounded using `HistoryLimits`, in which case the oldest
+/// operations are evicted as new ones are added. If configured with a
+/// `CoalescingPolicy`, adjacent single-grapheme edits are merged into a
+/// pending step, which is committed as soon as it's broken or navigated.
+///
+/// Each step records the cursor positions from before and after its operation
+/// was run, so that navigating the history can restore the cursor to where it
+/// was at that point in time.
 pub struct History {
-    previous: Vec<Box<dyn Operation>>,
-    next: Vec<Box<dyn Operation>>,
+    previous: VecDeque<Step>,
+    next: Vec<Step>,
     marked_position: Option<usize>,
+    limits: HistoryLimits,
+    size: usize,
+    coalescing: Option<CoalescingPolicy>,
+    pending: Option<PendingStep>,
+}
+
+// An operation and the cursor positions surrounding it.
+struct Step {
+    operation: Box<dyn Operation>,
+    cursor_before: Position,
+    cursor_after: Position,
+}
+
+// A history step that's still accepting coalesced edits.
+struct PendingStep {
+    group: OperationGroup,
+    cursor_before: Position,
+    cursor_after: Position,
+    kind: EditKind,
+    position: Position,
+    updated_at: Instant,
+}
+
+// How an incoming operation relates to the pending step.
+enum Coalescence {
+    Extend(EditKind, Position),
+    Start(EditKind, Position),
+    Skip,
 }
 
 impl History {
     /// Creates a new empty operation history.
     pub fn new() -> History {
         History {
-            previous: Vec::new(),
+            previous: VecDeque::new(),
             next: Vec::new(),
             marked_position: None,
+            limits: HistoryLimits::default(),
+            size: 0,
+            coalescing: None,
+            pending: None,
         }
     }
 
-    /// Store an operation that has already been run.
-    pub fn add(&mut self, operation: Box<dyn Operation>) {
-        self.previous.push(operation);
-        self.next.clear();
-
-        // Clear marked position if we've replaced a prior operation.
-        if let Some(position) = self.marked_position {
-            if position >= self.previous.len() {
-                self.marked_position = None
+    /// Store an operation that has already been run, along with
+    /// the cursor positions from before and after it was run.
+    pub fn add(
+        &mut self,
+        operation: Box<dyn Operation>,
+        cursor_before: Position,
+        cursor_after: Position,
+    ) {
+        self.size += operation.size();
+
+        match self.coalescence(operation.as_ref()) {
+            Coalescence::Extend(kind, position) => {
+                if let Some(ref mut pending) = self.pending {
+                    pending.group.add(operation);
+                    pending.cursor_after = cursor_after;
+                    pending.kind = kind;
+                    pending.position = position;
+                    pending.updated_at = Instant::now();
+                }
+            }
+            Coalescence::Start(kind, position) => {
+                self.commit_pending();
+
+                let mut group = OperationGroup::new();
+                group.add(operation);
+                self.pending = Some(PendingStep {
+                    group,
+                    cursor_before,
+                    cursor_after,
+                    kind,
+                    position,
+                    updated_at: Instant::now(),
+                });
+                self.discard_next();
+            }
+            Coalescence::Skip => {
+                self.commit_pending();
+                self.previous.push_back(Step {
+                    operation,
+                    cursor_before,
+                    cursor_after,
+                });
+                self.discard_next();
             }
         }
+
+        self.enforce_limits();
     }
 
-    /// Navigate the history backwards.
-    pub fn previous(&mut self) -> Option<Box<dyn Operation>> {
-        match self.previous.pop() {
-            Some(operation) => {
+    /// Navigate the history backwards. Returns the previous operation,
+    /// along with the cursor position from before it was run.
+    pub fn previous(&mut self) -> Option<(Box<dyn Operation>, Position)> {
+        self.commit_pending();
+
+        match self.previous.pop_back() {
+            Some(step) => {
                 // We've found a previous operation. Before we return it, store a
                 // clone of it so that it can be re-applied as a redo operation.
-                self.next.push(operation.clone_operation());
-                Some(operation)
+                self.next.push(Step {
+                    operation: step.operation.clone_operation(),
+                    cursor_before: step.cursor_before,
+                    cursor_after: step.cursor_after,
+                });
+                Some((step.operation, step.cursor_before))
             }
             None => None,
         }
     }
 
-    /// Navigate the history forwards.
-    pub fn next(&mut self) -> Option<Box<dyn Operation>> {
+    /// Navigate the history forwards. Returns the next operation,
+    /// along with the cursor position from after it was run.
+    pub fn next(&mut self) -> Option<(Box<dyn Operation>, Position)> {
+        self.commit_pending();
+
         match self.next.pop() {
-            Some(operation) => {
+            Some(step) => {
                 // We've found a subsequent operation. Before we return it, store a
                 // clone of it so that it can be re-applied as an undo operation, again.
-                self.previous.push(operation.clone_operation());
-                Some(operation)
+                self.previous.push_back(Step {
+                    operation: step.operation.clone_operation(),
+                    cursor_before: step.cursor_before,
+                    cursor_after: step.cursor_after,
+                });
+                Some((step.operation, step.cursor_after))
             }
             None => None,
         }
     }
 
     pub fn mark(&mut self) {
+        self.commit_pending();
         self.marked_position = Some(self.previous.len())
     }
 
     pub fn at_mark(&self) -> bool {
         if let Some(position) = self.marked_position {
-            self.previous.len() == position
+            self.len() == position
         } else {
             false
         }
     }
+
+    /// The approximate number of content bytes retained by the history,
+    /// including operations eligible to be redone.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn limits(&self) -> HistoryLimits {
+        self.limits
+    }
+
+    /// Replaces the history's limits, immediately
+    /// evicting any operations that exceed them.
+    pub fn set_limits(&mut self, limits: HistoryLimits) {
+        self.limits = limits;
+        self.enforce_limits();
+    }
+
+    pub fn coalescing(&self) -> Option<CoalescingPolicy> {
+        self.coalescing
+    }
+
+    /// Replaces the history's coalescing policy. Passing `None` disables
+    /// coalescing, committing any pending step as-is.
+    pub fn set_coalescing(&mut self, policy: Option<CoalescingPolicy>) {
+        if policy.is_none() {
+            self.commit_pending();
+        }
+
+        self.coalescing = policy;
+    }
+
+    // The number of undoable steps, including a pending one.
+    fn len(&self) -> usize {
+        self.previous.len() + usize::from(self.pending.is_some())
+    }
+
+    fn coalescence(&self, operation: &dyn Operation) -> Coalescence {
+        let (policy, edit) = match (self.coalescing, operation.grapheme_edit()) {
+            (Some(policy), Some(edit)) => (policy, edit),
+            _ => return Coalescence::Skip,
+        };
+
+        // Newlines always stand on their own.
+        if edit.grapheme.contains('\n') {
+            return Coalescence::Skip;
+        }
+
+        if let Some(ref pending) = self.pending {
+            let idle = policy
+                .idle_timeout
+                .is_some_and(|timeout| pending.updated_at.elapsed() > timeout);
+            let whitespace = edit.grapheme.chars().all(char::is_whitespace);
+
+            if !idle && !whitespace && adjacent(pending.kind, pending.position, &edit) {
+                return Coalescence::Extend(edit.kind, edit.position);
+            }
+        }
+
+        Coalescence::Start(edit.kind, edit.position)
+    }
+
+    fn commit_pending(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            self.previous.push_back(Step {
+                operation: Box::new(pending.group),
+                cursor_before: pending.cursor_before,
+                cursor_after: pending.cursor_after,
+            });
+        }
+    }
+
+    // Clears the redo stack, releasing its content, along
+    // with the marked position if it pointed into it.
+    fn discard_next(&mut self) {
+        for step in self.next.drain(..) {
+            self.size = self.size.saturating_sub(step.operation.size());
+        }
+
+        if let Some(position) = self.marked_position {
+            if position >= self.len() {
+                self.marked_position = None
+            }
+        }
+    }
+
+    fn exceeds_limits(&self) -> bool {
+        let operation_count = self.len() + self.next.len();
+
+        self.limits
+            .operations
+            .is_some_and(|max| operation_count > max)
+            || self.limits.size.is_some_and(|max| self.size > max)
+    }
+
+    // Evicts the oldest undo operations, followed by the most distant redo
+    // operations, until the history fits within its limits. The mark is
+    // shifted alongside evicted operations, and cleared once unreachable.
+    fn enforce_limits(&mut self) {
+        while self.exceeds_limits() {
+            if self.previous.is_empty() {
+                self.commit_pending();
+            }
+
+            if let Some(step) = self.previous.pop_front() {
+                self.size = self.size.saturating_sub(step.operation.size());
+                self.marked_position = match self.marked_position {
+                    Some(0) | None => None,
+                    Some(position) => Some(position - 1),
+                };
+            } else if !self.next.is_empty() {
+                let step = self.next.remove(0);
+                self.size = self.size.saturating_sub(step.operation.size());
+
+                if let Some(position) = self.marked_position {
+                    if position > self.next.len() {
+                        self.marked_position = None;
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+// Whether or not an edit continues a run of edits of the specified kind, the
+// last of which occurred at the specified position. Insertions must follow
+// one another, while deletions can be made either forwards or backwards.
+fn adjacent(kind: EditKind, position: Position, edit: &GraphemeEdit) -> bool {
+    if kind != edit.kind || position.line != edit.position.line {
+        return false;
+    }
+
+    match kind {
+        EditKind::Insert => edit.position.offset == position.offset + 1,
+        EditKind::Delete => {
+            edit.position.offset == position.offset || edit.position.offset + 1 == position.offset
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::History;
+    use super::{CoalescingPolicy, History, HistoryLimits};
+    use crate::buffer::operation::delete::Delete;
     use crate::buffer::operation::insert::Insert;
     use crate::buffer::operation::Operation;
-    use crate::buffer::{Buffer, Position};
+    use crate::buffer::{Buffer, Position, Range};
+    use std::thread;
+    use std::time::Duration;
+
+    // Runs single-character inserts for each character in the
+    // content, as though it were being typed at the position.
+    fn type_into(history: &mut History, buffer: &mut Buffer, content: &str, position: Position) {
+        for (index, character) in content.chars().enumerate() {
+            let mut operation = Insert::new(
+                character.to_string(),
+                Position {
+                    line: position.line,
+                    offset: position.offset + index,
+                },
+            );
+            operation.run(buffer);
+            history.add(Box::new(operation), Position::new(), Position::new());
+        }
+    }
 
     #[test]
     fn previous_and_next_return_the_correct_operations() {
@@ -91,14 +380,14 @@ mod tests {
         let insert_position = Position { line: 0, offset: 0 };
         let mut insert_operation = Insert::new("scribe".to_string(), insert_position);
         insert_operation.run(&mut buffer);
-        history.add(Box::new(insert_operation));
+        history.add(Box::new(insert_operation), insert_position, insert_position);
 
         // Make sure the buffer has the inserted content.
         assert_eq!(buffer.data(), "scribe");
 
         // Pull and reverse the last history item.
         match history.previous() {
-            Some(mut operation) => operation.reverse(&mut buffer),
+            Some((mut operation, _)) => operation.reverse(&mut buffer),
             None => (),
         };
 
@@ -107,7 +396,7 @@ mod tests {
 
         // Pull and run the next history item.
         match history.next() {
-            Some(mut operation) => operation.run(&mut buffer),
+            Some((mut operation, _)) => operation.run(&mut buffer),
             None => (),
         };
 
@@ -117,7 +406,7 @@ mod tests {
         // Pull and reverse the last history item, to make sure
         // the next function properly sets up the previous command.
         match history.previous() {
-            Some(mut operation) => operation.reverse(&mut buffer),
+            Some((mut operation, _)) => operation.reverse(&mut buffer),
             None => (),
         };
 
@@ -132,7 +421,7 @@ mod tests {
         // Add an insert operation to the history.
         let insert_position = Position { line: 0, offset: 0 };
         let insert_operation = Insert::new("scribe".to_string(), insert_position);
-        history.add(Box::new(insert_operation));
+        history.add(Box::new(insert_operation), insert_position, insert_position);
 
         // Pull the last history item. This will
         // add the operation to the redo stack.
@@ -140,7 +429,11 @@ mod tests {
 
         // Add another insert operation to the history.
         let second_insert_operation = Insert::new("scribe".to_string(), insert_position);
-        history.add(Box::new(second_insert_operation));
+        history.add(
+            Box::new(second_insert_operation),
+            insert_position,
+            insert_position,
+        );
 
         // Ensure there are no redo items.
         assert!(history.next().is_none());
@@ -162,7 +455,7 @@ mod tests {
         // Add an insert operation to the history.
         let insert_position = Position { line: 0, offset: 0 };
         let insert_operation = Insert::new("scribe".to_string(), insert_position);
-        history.add(Box::new(insert_operation));
+        history.add(Box::new(insert_operation), insert_position, insert_position);
 
         assert!(!history.at_mark());
     }
@@ -175,7 +468,7 @@ mod tests {
         // Add an insert operation to the history.
         let insert_position = Position { line: 0, offset: 0 };
         let insert_operation = Insert::new("scribe".to_string(), insert_position);
-        history.add(Box::new(insert_operation));
+        history.add(Box::new(insert_operation), insert_position, insert_position);
 
         // Reverse the operation.
         history.previous();
@@ -190,7 +483,7 @@ mod tests {
         // Add an insert operation to the history.
         let insert_position = Position { line: 0, offset: 0 };
         let insert_operation = Insert::new("scribe".to_string(), insert_position);
-        history.add(Box::new(insert_operation));
+        history.add(Box::new(insert_operation), insert_position, insert_position);
 
         // Mark the history.
         history.mark();
@@ -211,7 +504,7 @@ mod tests {
         // Add an insert operation to the history.
         let mut insert_position = Position { line: 0, offset: 0 };
         let mut insert_operation = Insert::new("scribe".to_string(), insert_position);
-        history.add(Box::new(insert_operation));
+        history.add(Box::new(insert_operation), insert_position, insert_position);
 
         // Mark the history.
         history.mark();
@@ -222,8 +515,279 @@ mod tests {
         // Add a replacement operation.
         insert_position = Position { line: 0, offset: 0 };
         insert_operation = Insert::new("scribe".to_string(), insert_position);
-        history.add(Box::new(insert_operation));
+        history.add(Box::new(insert_operation), insert_position, insert_position);
+
+        assert!(!history.at_mark());
+    }
+
+    #[test]
+    fn size_tracks_retained_content_across_undo_and_redo() {
+        let mut history = History::new();
+        let insert_position = Position { line: 0, offset: 0 };
+        history.add(
+            Box::new(Insert::new("scribe".to_string(), insert_position)),
+            insert_position,
+            insert_position,
+        );
+        history.add(
+            Box::new(Insert::new("amp".to_string(), insert_position)),
+            insert_position,
+            insert_position,
+        );
+        assert_eq!(history.size(), 9);
+
+        // Moving operations onto the redo stack retains them.
+        history.previous();
+        assert_eq!(history.size(), 9);
+
+        // Adding an operation discards the redo stack.
+        history.add(
+            Box::new(Insert::new("a".to_string(), insert_position)),
+            insert_position,
+            insert_position,
+        );
+        assert_eq!(history.size(), 7);
+    }
+
+    #[test]
+    fn adding_operations_beyond_the_operation_limit_evicts_the_oldest() {
+        let mut history = History::new();
+        history.set_limits(HistoryLimits {
+            operations: Some(2),
+            size: None,
+        });
+
+        let insert_position = Position { line: 0, offset: 0 };
+        for content in ["first", "second", "third"] {
+            history.add(
+                Box::new(Insert::new(content.to_string(), insert_position)),
+                insert_position,
+                insert_position,
+            );
+        }
+
+        assert_eq!(history.size(), "second".len() + "third".len());
+        assert!(history.previous().is_some());
+        assert!(history.previous().is_some());
+        assert!(history.previous().is_none());
+    }
+
+    #[test]
+    fn adding_operations_beyond_the_size_limit_evicts_the_oldest() {
+        let mut history = History::new();
+        history.set_limits(HistoryLimits {
+            operations: None,
+            size: Some(10),
+        });
+
+        let insert_position = Position { line: 0, offset: 0 };
+        history.add(
+            Box::new(Insert::new("scribe".to_string(), insert_position)),
+            insert_position,
+            insert_position,
+        );
+        history.add(
+            Box::new(Insert::new("library".to_string(), insert_position)),
+            insert_position,
+            insert_position,
+        );
+
+        assert_eq!(history.size(), 7);
+        assert!(history.previous().is_some());
+        assert!(history.previous().is_none());
+    }
+
+    #[test]
+    fn set_limits_evicts_distant_redo_operations_once_undo_stack_is_empty() {
+        let mut history = History::new();
+        let insert_position = Position { line: 0, offset: 0 };
+        history.add(
+            Box::new(Insert::new("first".to_string(), insert_position)),
+            insert_position,
+            insert_position,
+        );
+        history.add(
+            Box::new(Insert::new("second".to_string(), insert_position)),
+            insert_position,
+            insert_position,
+        );
+        history.mark();
+        history.previous();
+        history.previous();
+
+        history.set_limits(HistoryLimits {
+            operations: Some(1),
+            size: None,
+        });
+
+        // The nearest redo operation survives, but the marked state is gone.
+        assert_eq!(history.size(), "first".len());
+        assert!(history.next().is_some());
+        assert!(!history.at_mark());
+        assert!(history.next().is_none());
+    }
+
+    #[test]
+    fn evicting_operations_preserves_a_reachable_mark() {
+        let mut history = History::new();
+        history.set_limits(HistoryLimits {
+            operations: Some(2),
+            size: None,
+        });
+
+        let insert_position = Position { line: 0, offset: 0 };
+        history.add(
+            Box::new(Insert::new("first".to_string(), insert_position)),
+            insert_position,
+            insert_position,
+        );
+        history.add(
+            Box::new(Insert::new("second".to_string(), insert_position)),
+            insert_position,
+            insert_position,
+        );
+        history.mark();
+        history.add(
+            Box::new(Insert::new("third".to_string(), insert_position)),
+            insert_position,
+            insert_position,
+        );
+
+        // Undoing the last operation should return us to the marked state.
+        history.previous();
+        assert!(history.at_mark());
+    }
+
+    #[test]
+    fn evicting_the_marked_state_clears_the_mark() {
+        let mut history = History::new();
+        history.set_limits(HistoryLimits {
+            operations: Some(1),
+            size: None,
+        });
+        history.mark();
+
+        let insert_position = Position { line: 0, offset: 0 };
+        history.add(
+            Box::new(Insert::new("first".to_string(), insert_position)),
+            insert_position,
+            insert_position,
+        );
+        history.add(
+            Box::new(Insert::new("second".to_string(), insert_position)),
+            insert_position,
+            insert_position,
+        );
+
+        // Undoing everything that's left can't return us to the marked state.
+        history.previous();
+        assert!(!history.at_mark());
+    }
+
+    #[test]
+    fn coalescing_merges_adjacent_inserts_and_breaks_on_whitespace() {
+        let mut history = History::new();
+        let mut buffer = Buffer::new();
+        history.set_coalescing(Some(CoalescingPolicy::default()));
+        type_into(&mut history, &mut buffer, "scribe library", Position::new());
+
+        history.previous().unwrap().0.reverse(&mut buffer);
+        assert_eq!(buffer.data(), "scribe");
+
+        history.previous().unwrap().0.reverse(&mut buffer);
+        assert_eq!(buffer.data(), "");
+        assert!(history.previous().is_none());
+    }
+
+    #[test]
+    fn coalescing_breaks_on_cursor_jumps() {
+        let mut history = History::new();
+        let mut buffer = Buffer::new();
+        history.set_coalescing(Some(CoalescingPolicy::default()));
+        type_into(&mut history, &mut buffer, "scribe", Position::new());
+        type_into(&mut history, &mut buffer, "my", Position::new());
+        assert_eq!(buffer.data(), "myscribe");
+
+        history.previous().unwrap().0.reverse(&mut buffer);
+        assert_eq!(buffer.data(), "scribe");
+    }
+
+    #[test]
+    fn coalescing_never_merges_newlines() {
+        let mut history = History::new();
+        let mut buffer = Buffer::new();
+        history.set_coalescing(Some(CoalescingPolicy::default()));
+        type_into(&mut history, &mut buffer, "amp\n", Position::new());
+        type_into(
+            &mut history,
+            &mut buffer,
+            "scribe",
+            Position { line: 1, offset: 0 },
+        );
+
+        history.previous().unwrap().0.reverse(&mut buffer);
+        assert_eq!(buffer.data(), "amp\n");
+
+        history.previous().unwrap().0.reverse(&mut buffer);
+        assert_eq!(buffer.data(), "amp");
+    }
+
+    #[test]
+    fn coalescing_merges_backward_deletions() {
+        let mut history = History::new();
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe");
+        history.set_coalescing(Some(CoalescingPolicy::default()));
+
+        // Backspace from the end of the line.
+        for offset in (3..6).rev() {
+            let mut operation = Delete::new(Range::new(
+                Position { line: 0, offset },
+                Position {
+                    line: 0,
+                    offset: offset + 1,
+                },
+            ));
+            operation.run(&mut buffer);
+            history.add(Box::new(operation), Position::new(), Position::new());
+        }
+        assert_eq!(buffer.data(), "scr");
+
+        history.previous().unwrap().0.reverse(&mut buffer);
+        assert_eq!(buffer.data(), "scribe");
+        assert!(history.previous().is_none());
+    }
+
+    #[test]
+    fn coalescing_breaks_after_idle_timeout() {
+        let mut history = History::new();
+        let mut buffer = Buffer::new();
+        history.set_coalescing(Some(CoalescingPolicy {
+            idle_timeout: Some(Duration::from_millis(1)),
+        }));
+        type_into(&mut history, &mut buffer, "a", Position::new());
+        thread::sleep(Duration::from_millis(5));
+        type_into(
+            &mut history,
+            &mut buffer,
+            "b",
+            Position { line: 0, offset: 1 },
+        );
+
+        history.previous().unwrap().0.reverse(&mut buffer);
+        assert_eq!(buffer.data(), "a");
+    }
 
+    #[test]
+    fn history_is_not_at_mark_with_a_pending_coalesced_step() {
+        let mut history = History::new();
+        let mut buffer = Buffer::new();
+        history.set_coalescing(Some(CoalescingPolicy::default()));
+        history.mark();
+        type_into(&mut history, &mut buffer, "ab", Position::new());
         assert!(!history.at_mark());
+
+        history.previous();
+        assert!(history.at_mark());
     }
 }
@@ -1,5 +1,5 @@
-use crate::buffer::operation::Operation;
-use crate::buffer::{Buffer, Position, Range};
+use crate::buffer::operation::{EditKind, GraphemeEdit, Operation};
+use crate::buffer::{Buffer, MacroStep, Position, Range};
 use std::clone::Clone;
 use std::convert::Into;
 use unicode_segmentation::UnicodeSegmentation;
@@ -20,6 +20,14 @@ pub struct Insert {
 
 impl Operation for Insert {
     fn run(&mut self, buffer: &mut Buffer) {
+        // Out of bounds inserts are ignored, and don't affect parsing.
+        if buffer.data.borrow().in_bounds(&self.position) {
+            buffer.parse_cache.borrow_mut().invalidate(
+                self.position.line,
+                0,
+                self.content.matches('\n').count(),
+            );
+        }
         buffer
             .data
             .borrow_mut()
@@ -47,7 +55,7 @@ impl Operation for Insert {
         } else {
             // If there are multiple lines, the end of the range doesn't
             // need to consider the original insertion location.
-            match self.content.split('\n').last() {
+            match self.content.split('\n').next_back() {
                 Some(line) => line.graphemes(true).count(),
                 None => return,
             }
@@ -63,6 +71,10 @@ impl Operation for Insert {
 
         // Remove the content we'd previously inserted.
         buffer.data.borrow_mut().delete(&range);
+        buffer
+            .parse_cache
+            .borrow_mut()
+            .invalidate(self.position.line, line_count - 1, 0);
 
         // Run the change callback, if present.
         if let Some(ref callback) = buffer.change_callback {
@@ -73,10 +85,26 @@ impl Operation for Insert {
     fn clone_operation(&self) -> Box<dyn Operation> {
         Box::new(self.clone())
     }
+
+    fn size(&self) -> usize {
+        self.content.len()
+    }
+
+    fn grapheme_edit(&self) -> Option<GraphemeEdit<'_>> {
+        if self.content.graphemes(true).count() != 1 {
+            return None;
+        }
+
+        Some(GraphemeEdit {
+            kind: EditKind::Insert,
+            position: self.position,
+            grapheme: &self.content,
+        })
+    }
 }
 
 impl Insert {
-    /// Creates a new empty insert operation.
+    /// Creates a new insert operation.
     pub fn new(content: String, position: Position) -> Insert {
         Insert { content, position }
     }
@@ -95,16 +123,12 @@ impl Buffer {
     /// assert_eq!(buffer.data(), "scribe");
     /// ```
     pub fn insert<T: Into<String>>(&mut self, data: T) {
-        // Build and run an insert operation.
-        let mut op = Insert::new(data.into(), self.cursor.position);
-        op.run(self);
-
-        // Store the operation in the history
-        // object so that it can be undone.
-        match self.operation_group {
-            Some(ref mut group) => group.add(Box::new(op)),
-            None => self.history.add(Box::new(op)),
-        };
+        let data = data.into();
+        self.record_macro_step(MacroStep::Insert(data.clone()));
+
+        // Build and run an insert operation, storing it in history.
+        let op = Insert::new(data, self.cursor.position);
+        self.run_operation(Box::new(op));
     }
 }
 
@@ -1,5 +1,8 @@
+pub use self::delete::Delete;
 pub use self::group::OperationGroup;
-use crate::buffer::Buffer;
+pub use self::insert::Insert;
+pub use self::replace::Replace;
+use crate::buffer::{Buffer, Position};
 
 mod delete;
 pub mod group;
@@ -9,15 +12,103 @@ mod replace;
 
 /// A reversible buffer operation.
 ///
-/// Operations are an internal way of encapsulating an action on a buffer
-/// that can be run and reversed. They're directly tied to scribe's history
-/// functionality, which uses the trait's methods to run and reverse these.
+/// Operations encapsulate an action on a buffer that can be run and reversed.
+/// They're directly tied to scribe's history functionality, which uses the
+/// trait's methods to run and reverse these.
 ///
-/// Types that implement this trait are responsible for adding methods to the
-/// Buffer type to expose their functionality; these should build, run, and
-/// add the operation objects to the buffer history.
+/// Built-in operations are exposed through Buffer methods (e.g. `insert`),
+/// which build, run, and add the operation objects to the buffer history.
+/// Custom operations can be run the same way using `Buffer::apply_operation`.
+/// Since buffer data can't be modified directly, custom operations should be
+/// composed of the built-in `Insert`, `Delete`, and `Replace` operations,
+/// which run the buffer's `change_callback` whenever they're run or reversed.
+///
+/// # Examples
+///
+/// ```
+/// use scribe::Buffer;
+/// use scribe::buffer::{Delete, Insert, Operation, Position, Range};
+///
+/// // Converts a range of the buffer to upper case.
+/// #[derive(Clone)]
+/// struct Uppercase {
+///     range: Range,
+///     content: Option<String>,
+/// }
+///
+/// impl Operation for Uppercase {
+///     fn run(&mut self, buffer: &mut Buffer) {
+///         let content = buffer.read(&self.range).unwrap_or_default();
+///         Delete::new(self.range.clone()).run(buffer);
+///         Insert::new(content.to_uppercase(), self.range.start()).run(buffer);
+///         self.content = Some(content);
+///     }
+///
+///     fn reverse(&mut self, buffer: &mut Buffer) {
+///         if let Some(ref content) = self.content {
+///             Insert::new(content.to_uppercase(), self.range.start()).reverse(buffer);
+///             Insert::new(content.clone(), self.range.start()).run(buffer);
+///         }
+///     }
+///
+///     fn clone_operation(&self) -> Box<dyn Operation> {
+///         Box::new(self.clone())
+///     }
+///
+///     fn size(&self) -> usize {
+///         self.content.as_ref().map(|c| c.len()).unwrap_or(0)
+///     }
+/// }
+///
+/// let mut buffer = Buffer::new();
+/// buffer.insert("scribe library");
+/// buffer.apply_operation(Box::new(Uppercase {
+///     range: Range::new(
+///         Position{ line: 0, offset: 0 },
+///         Position{ line: 0, offset: 6 }
+///     ),
+///     content: None,
+/// }));
+/// assert_eq!(buffer.data(), "SCRIBE library");
+///
+/// buffer.undo();
+/// assert_eq!(buffer.data(), "scribe library");
+/// ```
 pub trait Operation {
+    /// Applies the operation to the buffer.
     fn run(&mut self, buffer: &mut Buffer);
+
+    /// Reverts the changes made to the buffer by a prior call to `run`.
     fn reverse(&mut self, buffer: &mut Buffer);
+
+    /// Clones the operation, allowing history to retain a copy of it.
     fn clone_operation(&self) -> Box<dyn Operation>;
+
+    /// The approximate number of bytes of buffer content retained by the
+    /// operation, used to enforce history limits. Operations that don't
+    /// provide one aren't counted towards the history's size limit.
+    fn size(&self) -> usize {
+        0
+    }
+
+    /// Describes the operation as an edit to a single grapheme cluster, if
+    /// applicable, allowing history to coalesce it with adjacent edits.
+    fn grapheme_edit(&self) -> Option<GraphemeEdit<'_>> {
+        None
+    }
+}
+
+/// The kind of change described by a `GraphemeEdit`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EditKind {
+    Insert,
+    Delete,
+}
+
+/// An insertion or deletion of a single grapheme cluster at a position.
+#[derive(Debug, PartialEq)]
+pub struct GraphemeEdit<'a> {
+    pub kind: EditKind,
+    pub position: Position,
+    pub grapheme: &'a str,
 }
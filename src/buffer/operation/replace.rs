@@ -5,14 +5,13 @@ use std::clone::Clone;
 use std::convert::Into;
 use std::rc::Rc;
 
-/// A reversible buffer insert operation.
+/// A reversible buffer replace operation.
 ///
-/// Inserts the provided content at the specified position. Tracks both, and reverses
-/// the operation by calculating the content's start and end positions (range), relative
-/// to its inserted location, and removing said range from the underlying buffer.
+/// Replaces the buffer's entire contents with the provided content. Tracks both
+/// the old and new content, and reverses the operation by restoring the former.
 ///
 /// If the buffer is configured with a `change_callback`, it will be called with
-/// the position of this operation when it is run or reversed.
+/// a zero position when this operation is run or reversed.
 #[derive(Clone)]
 pub struct Replace {
     old_content: String,
@@ -31,10 +30,14 @@ impl Operation for Replace {
     fn clone_operation(&self) -> Box<dyn Operation> {
         Box::new(self.clone())
     }
+
+    fn size(&self) -> usize {
+        self.old_content.len() + self.new_content.len()
+    }
 }
 
 impl Replace {
-    /// Creates a new empty insert operation.
+    /// Creates a new replace operation.
     pub fn new(old_content: String, new_content: String) -> Replace {
         Replace {
             old_content,
@@ -81,15 +84,8 @@ impl Buffer {
             return;
         }
 
-        // Build and run an insert operation.
-        let mut op = Replace::new(self.data(), content.into());
-        op.run(self);
-
-        // Store the operation in the history object so that it can be undone.
-        match self.operation_group {
-            Some(ref mut group) => group.add(Box::new(op)),
-            None => self.history.add(Box::new(op)),
-        };
+        // Build and run a replace operation, storing it in history.
+        self.apply_operation(Box::new(Replace::new(old_content, content.into())));
     }
 }
 
@@ -109,6 +105,7 @@ fn replace_content(content: String, buffer: &mut Buffer) {
     // Do the replacement.
     buffer.data = data;
     buffer.cursor = cursor;
+    buffer.parse_cache.borrow_mut().clear();
 
     // Run the change callback, if present.
     if let Some(ref callback) = buffer.change_callback {
@@ -0,0 +1,356 @@
+use crate::buffer::{Buffer, Delete, Insert, Position, Range};
+use std::mem;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A user-supplied function run against a buffer's content when it's
+/// saved, returning the content to be saved in its place.
+pub type SaveTransform = Box<dyn Fn(&str) -> String>;
+
+/// Line terminators that can be written when saving a buffer.
+/// Buffers use `\n` in-memory; files consistently using another
+/// terminator are converted when loaded, and converted back when saved.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl LineEnding {
+    /// The terminator's characters.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+
+    /// Determines the terminator used by every line of the content,
+    /// returning `None` if it has only a single line, or mixes terminators.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::buffer::LineEnding;
+    ///
+    /// assert_eq!(LineEnding::detect("scribe\r\nlibrary\r\n"), Some(LineEnding::CrLf));
+    /// assert_eq!(LineEnding::detect("scribe\rlibrary"), Some(LineEnding::Cr));
+    /// assert_eq!(LineEnding::detect("scribe\r\nlibrary\n"), None);
+    /// assert_eq!(LineEnding::detect("scribe"), None);
+    /// ```
+    pub fn detect(content: &str) -> Option<LineEnding> {
+        let mut line_ending = None;
+        let mut bytes = content.bytes().peekable();
+
+        while let Some(byte) = bytes.next() {
+            let terminator = match byte {
+                b'\r' if bytes.next_if_eq(&b'\n').is_some() => LineEnding::CrLf,
+                b'\r' => LineEnding::Cr,
+                b'\n' => LineEnding::Lf,
+                _ => continue,
+            };
+
+            match line_ending {
+                Some(previous) if previous != terminator => return None,
+                _ => line_ending = Some(terminator),
+            }
+        }
+
+        line_ending
+    }
+}
+
+/// Adjustments made to a buffer's content when it's saved.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SaveOptions {
+    /// Remove whitespace from the end of every line.
+    pub trim_trailing_whitespace: bool,
+
+    /// Ensure non-empty content ends with a newline.
+    pub insert_final_newline: bool,
+
+    /// Remove extra newlines from the end of the content, leaving one.
+    /// Combined with `insert_final_newline`, this ensures non-empty
+    /// content ends with exactly one newline.
+    pub trim_final_newlines: bool,
+
+    /// Convert `\r\n` and lone `\r` terminators in the content to `\n`.
+    /// Use `line_ending` to control the terminator written to disk.
+    pub normalize_line_endings: bool,
+
+    /// The terminator written in place of each `\n`. Defaults to `\n`, or
+    /// to the terminator detected when the buffer is loaded from a file.
+    pub line_ending: Option<LineEnding>,
+
+    /// Write a UTF-8 byte order mark at the start of the file.
+    pub byte_order_mark: bool,
+}
+
+impl Buffer {
+    // Applies the buffer's save transforms and options to its content, as a
+    // single operation, so that it matches what's about to be written to disk.
+    pub(crate) fn apply_save_options(&mut self) {
+        // These adjustments aren't made by the user, and
+        // shouldn't end any in-progress macro recording.
+        let macro_recording = self.macro_recording.take();
+        let cursor = self.cursor.position;
+        self.in_operation_group(Buffer::transform_for_save);
+        if !self.cursor.move_to(cursor) {
+            self.cursor.move_to(Position {
+                line: cursor.line,
+                offset: self.line_length(cursor.line).unwrap_or(0),
+            });
+        }
+        self.macro_recording = macro_recording;
+    }
+
+    // Runs the save transforms, followed by the content-changing save options.
+    fn transform_for_save(&mut self) {
+        let transforms = mem::take(&mut self.save_transforms);
+        for transform in transforms.iter() {
+            let data = self.data();
+            self.replace_data_minimally(&data, &transform(&data));
+        }
+        self.save_transforms = transforms;
+
+        if self.save_options.normalize_line_endings {
+            let data = self.data();
+            if data.contains('\r') {
+                let content = data.replace("\r\n", "\n").replace('\r', "\n");
+                self.replace_data_minimally(&data, &content);
+            }
+        }
+
+        if self.save_options.trim_trailing_whitespace {
+            let data = self.data();
+            for (line, content) in data.split('\n').enumerate() {
+                let length = content.graphemes(true).count();
+                let trimmed_length = content.trim_end().graphemes(true).count();
+
+                if trimmed_length < length {
+                    self.apply_operation(Box::new(Delete::new(Range::new(
+                        Position {
+                            line,
+                            offset: trimmed_length,
+                        },
+                        Position {
+                            line,
+                            offset: length,
+                        },
+                    ))));
+                }
+            }
+        }
+
+        if self.save_options.trim_final_newlines {
+            let data = self.data();
+            let content = data.trim_end_matches('\n');
+            if data.len() - content.len() > 1 {
+                let end = self.end_position();
+                self.apply_operation(Box::new(Delete::new(Range::new(
+                    Position {
+                        line: content.matches('\n').count() + 1,
+                        offset: 0,
+                    },
+                    end,
+                ))));
+            }
+        }
+
+        if self.save_options.insert_final_newline {
+            let data = self.data();
+            if !data.is_empty() && !data.ends_with('\n') {
+                let end = self.end_position();
+                self.apply_operation(Box::new(Insert::new("\n".to_string(), end)));
+            }
+        }
+    }
+
+    // Replaces the buffer's content, only modifying lines that differ,
+    // so that unchanged lines retain their parse state and positions.
+    fn replace_data_minimally(&mut self, data: &str, content: &str) {
+        let range = Range::new(Position::new(), self.end_position());
+        self.replace_range_minimally(&range, data, content);
+    }
+
+    // The buffer's content, encoded as it should be written to disk.
+    pub(crate) fn encoded_data(&self) -> Vec<u8> {
+        let mut data = self.data();
+        if let Some(line_ending) = self.save_options.line_ending {
+            data = data.replace('\n', line_ending.as_str());
+        }
+        if self.save_options.byte_order_mark {
+            data.insert(0, '\u{feff}');
+        }
+
+        data.into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LineEnding, SaveOptions};
+    use crate::buffer::{Buffer, Position};
+    use std::cell::RefCell;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+    use std::{env, fs};
+
+    // A file path in the temporary directory, unique to the test.
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("scribe_{}", name))
+    }
+
+    fn save_to(buffer: &mut Buffer, name: &str) -> Vec<u8> {
+        let path = temp_path(name);
+        buffer.path = Some(path.clone());
+        buffer.save().unwrap();
+        let data = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        data
+    }
+
+    #[test]
+    fn save_trims_trailing_whitespace_and_inserts_final_newline() {
+        let mut buffer = Buffer::new();
+        buffer.save_options = SaveOptions {
+            trim_trailing_whitespace: true,
+            insert_final_newline: true,
+            ..Default::default()
+        };
+        buffer.insert("scribe  \n\t\nlibrary\t");
+        buffer.cursor.move_to(Position { line: 0, offset: 8 });
+
+        assert_eq!(
+            save_to(&mut buffer, "save_options_trim"),
+            b"scribe\n\nlibrary\n"
+        );
+        assert_eq!(buffer.data(), "scribe\n\nlibrary\n");
+        assert_eq!(*buffer.cursor, Position { line: 0, offset: 6 });
+        assert!(!buffer.modified());
+
+        // The adjustments are undone as a single step.
+        buffer.undo();
+        assert_eq!(buffer.data(), "scribe  \n\t\nlibrary\t");
+    }
+
+    #[test]
+    fn save_does_not_modify_buffers_without_options() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe  ");
+
+        assert_eq!(save_to(&mut buffer, "save_options_none"), b"scribe  ");
+        buffer.undo();
+        assert_eq!(buffer.data(), "");
+    }
+
+    #[test]
+    fn save_leaves_exactly_one_final_newline() {
+        let mut buffer = Buffer::new();
+        buffer.save_options = SaveOptions {
+            trim_final_newlines: true,
+            ..Default::default()
+        };
+        buffer.insert("scribe\n\n\n");
+
+        assert_eq!(save_to(&mut buffer, "save_options_newlines"), b"scribe\n");
+        assert!(!buffer.modified());
+    }
+
+    #[test]
+    fn save_normalizes_line_endings() {
+        let mut buffer = Buffer::new();
+        buffer.save_options = SaveOptions {
+            normalize_line_endings: true,
+            trim_trailing_whitespace: true,
+            ..Default::default()
+        };
+        buffer.insert("scribe \r\nlibrary\reditor");
+
+        assert_eq!(
+            save_to(&mut buffer, "save_options_line_endings"),
+            b"scribe\nlibrary\neditor"
+        );
+    }
+
+    #[test]
+    fn save_runs_transforms_before_options_as_a_single_operation() {
+        let mut buffer = Buffer::new();
+        buffer.save_options.insert_final_newline = true;
+        buffer
+            .save_transforms
+            .push(Box::new(|content| content.to_uppercase()));
+        buffer
+            .save_transforms
+            .push(Box::new(|content| content.replace("SCRIBE", "scribe")));
+        buffer.insert("scribe library");
+
+        assert_eq!(
+            save_to(&mut buffer, "save_options_transforms"),
+            b"scribe LIBRARY\n"
+        );
+        assert_eq!(buffer.data(), "scribe LIBRARY\n");
+        assert!(!buffer.modified());
+        assert_eq!(buffer.save_transforms.len(), 2);
+
+        buffer.undo();
+        assert_eq!(buffer.data(), "scribe library");
+        assert!(buffer.modified());
+    }
+
+    #[test]
+    fn save_transforms_only_modify_changed_lines() {
+        let mut buffer = Buffer::new();
+        buffer
+            .save_transforms
+            .push(Box::new(|content| content.replace("library", "LIBRARY")));
+        buffer.insert("scribe\nlibrary\neditor");
+
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let callback_changes = changes.clone();
+        buffer.change_callback = Some(Box::new(move |position| {
+            callback_changes.borrow_mut().push(position)
+        }));
+
+        assert_eq!(
+            save_to(&mut buffer, "save_options_minimal_transforms"),
+            b"scribe\nLIBRARY\neditor"
+        );
+        assert!(changes.borrow().iter().all(|position| position.line == 1));
+        assert!(!changes.borrow().is_empty());
+    }
+
+    #[test]
+    fn save_writes_line_endings_and_byte_order_mark() {
+        let mut buffer = Buffer::new();
+        buffer.save_options = SaveOptions {
+            line_ending: Some(LineEnding::CrLf),
+            byte_order_mark: true,
+            ..Default::default()
+        };
+        buffer.insert("scribe\nlibrary\n");
+
+        assert_eq!(
+            save_to(&mut buffer, "save_options_encoding"),
+            "\u{feff}scribe\r\nlibrary\r\n".as_bytes()
+        );
+        assert_eq!(buffer.data(), "scribe\nlibrary\n");
+    }
+
+    #[test]
+    fn save_preserves_the_line_endings_of_loaded_files() {
+        let path = temp_path("save_options_crlf");
+        fs::write(&path, "scribe \r\nlibrary\r\n").unwrap();
+        let mut buffer = Buffer::from_file(&path).unwrap();
+        buffer.save_options.trim_trailing_whitespace = true;
+
+        assert_eq!(buffer.data(), "scribe \nlibrary\n");
+        assert_eq!(buffer.save_options.line_ending, Some(LineEnding::CrLf));
+        assert_eq!(
+            save_to(&mut buffer, "save_options_crlf"),
+            b"scribe\r\nlibrary\r\n"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use crate::buffer::{Buffer, LineRange, Position, Token};
+use crate::errors::*;
+use syntect::parsing::{ScopeStack, SyntaxSet};
+use unicode_segmentation::UnicodeSegmentation;
+
+impl Buffer {
+    /// The full scope stack for the grapheme at the specified position. At the
+    /// end of a line, the scope of the line's last grapheme is used, so that
+    /// a cursor following a token is considered to be within it.
+    ///
+    /// Returns an `ErrorKind::MissingScope` error if there's no token at or
+    /// immediately before the position (e.g. on an empty line, or outside of
+    /// the buffer). The syntax set should be the one from which the buffer's
+    /// syntax definition was sourced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::Position;
+    /// use syntect::parsing::SyntaxSet;
+    ///
+    /// let syntax_set = SyntaxSet::load_defaults_newlines();
+    /// let mut buffer = Buffer::new();
+    /// buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+    /// buffer.insert("let x = \"scribe\";");
+    ///
+    /// let scope = buffer.scope_at(Position{ line: 0, offset: 10 }, &syntax_set).unwrap();
+    /// assert_eq!(scope, "source.rust string.quoted.double.rust".parse().unwrap());
+    /// ```
+    pub fn scope_at(&self, position: Position, syntax_set: &SyntaxSet) -> Result<ScopeStack> {
+        let tokens = self.tokens(syntax_set);
+        let mut iterator = tokens.iter_lines(&LineRange::new(position.line, position.line + 1))?;
+
+        let mut preceding_scope = None;
+        for token in iterator.by_ref() {
+            if let Token::Lexeme(lexeme) = token {
+                if lexeme.position > position {
+                    break;
+                }
+
+                let end = lexeme.position.offset + lexeme.value.graphemes(true).count();
+                if position.offset < end {
+                    return Ok(lexeme.scope);
+                } else if position.offset == end {
+                    preceding_scope = Some(lexeme.scope);
+                }
+            }
+        }
+        if let Some(error) = iterator.error.take() {
+            return Err(error);
+        }
+
+        preceding_scope.ok_or_else(|| ErrorKind::MissingScope.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::{Buffer, Position};
+    use crate::errors::*;
+    use syntect::parsing::SyntaxSet;
+
+    #[test]
+    fn scope_at_includes_enclosing_scopes_from_earlier_lines() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert("/*\nscribe\n*/");
+        let scope = buffer
+            .scope_at(Position { line: 1, offset: 2 }, &syntax_set)
+            .unwrap();
+
+        assert_eq!(scope, "source.rust comment.block.rust".parse().unwrap());
+    }
+
+    #[test]
+    fn scope_at_uses_the_preceding_token_at_the_end_of_a_line() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert("// scribe\n");
+        let scope = buffer
+            .scope_at(Position { line: 0, offset: 9 }, &syntax_set)
+            .unwrap();
+
+        assert_eq!(
+            scope,
+            "source.rust comment.line.double-slash.rust"
+                .parse()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn scope_at_raises_missing_scope_without_a_token() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert("scribe\n");
+
+        // An empty line.
+        assert!(matches!(
+            buffer.scope_at(Position { line: 1, offset: 0 }, &syntax_set),
+            Err(Error(ErrorKind::MissingScope, _))
+        ));
+
+        // Past the end of the line.
+        assert!(matches!(
+            buffer.scope_at(
+                Position {
+                    line: 0,
+                    offset: 10
+                },
+                &syntax_set
+            ),
+            Err(Error(ErrorKind::MissingScope, _))
+        ));
+    }
+}
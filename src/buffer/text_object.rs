@@ -0,0 +1,639 @@
+use crate::buffer::bracket::{matching_delimiter, Delimiter, BRACKETS};
+use crate::buffer::{Buffer, Position, Range, Token};
+use crate::errors::*;
+use syntect::parsing::SyntaxSet;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Structured regions of text that can be selected around a position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextObject {
+    /// A run of word characters (alphanumerics and underscores), or
+    /// of other non-whitespace characters.
+    Word,
+
+    /// A run of non-whitespace characters.
+    BigWord,
+
+    /// Text ending with `.`, `!`, or `?` (and any closing brackets or
+    /// quotes), followed by whitespace or the end of the paragraph.
+    Sentence,
+
+    /// A run of non-blank lines, or of blank lines.
+    Paragraph,
+
+    /// The innermost pair of the specified bracket (either its opening or
+    /// closing character) enclosing the position, ignoring brackets in
+    /// strings and comments.
+    Bracket(char),
+
+    /// The innermost string delimited by the specified quote character
+    /// enclosing the position. Requires a syntax definition that scopes
+    /// strings.
+    Quote(char),
+
+    /// The region covered by the innermost syntax scope at the position,
+    /// such as a string literal or a function body.
+    Scope,
+}
+
+/// The portion of a text object to select.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Extent {
+    /// Excludes surrounding whitespace, brackets, quotes, and punctuation.
+    Inside,
+
+    /// Includes surrounding brackets, quotes, and punctuation, as well as
+    /// trailing (or failing that, leading) whitespace for words, sentences,
+    /// and paragraphs.
+    Around,
+}
+
+impl Buffer {
+    /// Finds the range of the text object around the specified position,
+    /// returning `None` if the position isn't in one.
+    ///
+    /// Bracket, quote, and scope objects use the scopes produced by
+    /// tokenizing the buffer with its syntax definition (or plain text, if it
+    /// doesn't have one). The syntax set should be the one from which the
+    /// buffer's syntax definition was sourced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::{Extent, Position, Range, TextObject};
+    /// use syntect::parsing::SyntaxSet;
+    ///
+    /// let syntax_set = SyntaxSet::load_defaults_newlines();
+    /// let mut buffer = Buffer::new();
+    /// buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+    /// buffer.insert("scribe(\"editor library\");");
+    /// let position = Position{ line: 0, offset: 10 };
+    ///
+    /// assert_eq!(
+    ///     buffer.text_object(position, TextObject::Word, Extent::Around, &syntax_set).unwrap(),
+    ///     Some(Range::new(
+    ///         Position{ line: 0, offset: 8 },
+    ///         Position{ line: 0, offset: 15 }
+    ///     ))
+    /// );
+    /// assert_eq!(
+    ///     buffer.text_object(position, TextObject::Quote('"'), Extent::Inside, &syntax_set).unwrap(),
+    ///     Some(Range::new(
+    ///         Position{ line: 0, offset: 8 },
+    ///         Position{ line: 0, offset: 22 }
+    ///     ))
+    /// );
+    /// assert_eq!(
+    ///     buffer.text_object(position, TextObject::Bracket(')'), Extent::Around, &syntax_set).unwrap(),
+    ///     Some(Range::new(
+    ///         Position{ line: 0, offset: 6 },
+    ///         Position{ line: 0, offset: 24 }
+    ///     ))
+    /// );
+    /// ```
+    pub fn text_object(
+        &self,
+        position: Position,
+        object: TextObject,
+        extent: Extent,
+        syntax_set: &SyntaxSet,
+    ) -> Result<Option<Range>> {
+        let data = self.data();
+        let lines: Vec<&str> = data.split('\n').collect();
+
+        Ok(match object {
+            TextObject::Word => word(&lines, position, extent, word_class),
+            TextObject::BigWord => word(&lines, position, extent, |g| {
+                if is_whitespace(g) {
+                    0
+                } else {
+                    1
+                }
+            }),
+            TextObject::Sentence => sentence(&lines, position, extent),
+            TextObject::Paragraph => paragraph(&lines, position, extent),
+            TextObject::Bracket(bracket) => {
+                let delimiters = self.delimiters(syntax_set)?;
+                enclosing_bracket(&delimiters, position, bracket)
+                    .map(|(open, close)| pair_range(&delimiters[open], &delimiters[close], extent))
+            }
+            TextObject::Quote(quote) => {
+                let delimiters = self.delimiters(syntax_set)?;
+                enclosing_string(&delimiters, position, quote)
+                    .map(|(open, close)| pair_range(&delimiters[open], &delimiters[close], extent))
+            }
+            TextObject::Scope => self.scope_range(position, extent, syntax_set)?,
+        })
+    }
+
+    // Finds the region covered by the innermost scope at the position.
+    fn scope_range(
+        &self,
+        position: Position,
+        extent: Extent,
+        syntax_set: &SyntaxSet,
+    ) -> Result<Option<Range>> {
+        let tokens = self.tokens(syntax_set);
+        let mut iterator = tokens.iter()?;
+
+        // Collect lexemes as (start, end, scope) tuples.
+        let mut lexemes = Vec::new();
+        for token in iterator.by_ref() {
+            if let Token::Lexeme(lexeme) = token {
+                let end = Position {
+                    line: lexeme.position.line,
+                    offset: lexeme.position.offset + lexeme.value.graphemes(true).count(),
+                };
+                lexemes.push((lexeme.position, end, lexeme.scope));
+            }
+        }
+        if let Some(error) = iterator.error.take() {
+            return Err(error);
+        }
+
+        let index = match lexemes
+            .iter()
+            .position(|(start, end, _)| *start <= position && position < *end)
+        {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+
+        // Extend the region over adjacent lexemes sharing the innermost scope.
+        let scope = lexemes[index].2.as_slice();
+        let shares_scope = |i: &usize| lexemes[*i].2.as_slice().starts_with(scope);
+        let mut first = (0..index)
+            .rev()
+            .take_while(shares_scope)
+            .last()
+            .unwrap_or(index);
+        let mut last = (index + 1..lexemes.len())
+            .take_while(shares_scope)
+            .last()
+            .unwrap_or(index);
+
+        // Exclude punctuation (e.g. quotes or braces) delimiting the scope.
+        if extent == Extent::Inside {
+            let is_punctuation = |i: usize| {
+                lexemes[i].2.as_slice().len() > scope.len()
+                    && lexemes[i].2.as_slice()[scope.len()]
+                        .build_string()
+                        .starts_with("punctuation.")
+            };
+            if first < last && is_punctuation(first) {
+                first += 1;
+            }
+            if first < last && is_punctuation(last) {
+                last -= 1;
+            }
+        }
+
+        Ok(Some(Range::new(lexemes[first].0, lexemes[last].1)))
+    }
+}
+
+fn is_whitespace(grapheme: &str) -> bool {
+    grapheme.chars().all(char::is_whitespace)
+}
+
+// Classifies graphemes as whitespace (0), word characters (1), or other (2).
+fn word_class(grapheme: &str) -> u8 {
+    if is_whitespace(grapheme) {
+        0
+    } else if grapheme.chars().any(|c| c.is_alphanumeric() || c == '_') {
+        1
+    } else {
+        2
+    }
+}
+
+// Finds the bounds of the run of same-class graphemes including the index.
+fn run<F: Fn(&str) -> u8>(graphemes: &[&str], index: usize, class: &F) -> (usize, usize) {
+    let kind = class(graphemes[index]);
+    let start = (0..index)
+        .rev()
+        .take_while(|&i| class(graphemes[i]) == kind)
+        .last()
+        .unwrap_or(index);
+    let end = (index..graphemes.len())
+        .take_while(|&i| class(graphemes[i]) == kind)
+        .last()
+        .map_or(index, |i| i + 1);
+
+    (start, end)
+}
+
+fn word<F: Fn(&str) -> u8>(
+    lines: &[&str],
+    position: Position,
+    extent: Extent,
+    class: F,
+) -> Option<Range> {
+    let graphemes: Vec<&str> = lines.get(position.line)?.graphemes(true).collect();
+    if position.offset >= graphemes.len() {
+        return None;
+    }
+
+    let (mut start, mut end) = run(&graphemes, position.offset, &class);
+    if extent == Extent::Around {
+        if class(graphemes[start]) == 0 {
+            // Whitespace is selected along with the following word.
+            if end < graphemes.len() {
+                end = run(&graphemes, end, &class).1;
+            }
+        } else if end < graphemes.len() && class(graphemes[end]) == 0 {
+            end = run(&graphemes, end, &class).1;
+        } else if start > 0 && class(graphemes[start - 1]) == 0 {
+            start = run(&graphemes, start - 1, &class).0;
+        }
+    }
+
+    Some(Range::new(
+        Position {
+            line: position.line,
+            offset: start,
+        },
+        Position {
+            line: position.line,
+            offset: end,
+        },
+    ))
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+// Finds the bounds of the run of (non-)blank lines including the line.
+fn line_run(lines: &[&str], line: usize) -> (usize, usize) {
+    let blank = is_blank(lines[line]);
+    let start = (0..line)
+        .rev()
+        .take_while(|&l| is_blank(lines[l]) == blank)
+        .last()
+        .unwrap_or(line);
+    let end = (line..lines.len())
+        .take_while(|&l| is_blank(lines[l]) == blank)
+        .last()
+        .map_or(line, |l| l + 1);
+
+    (start, end)
+}
+
+fn paragraph(lines: &[&str], position: Position, extent: Extent) -> Option<Range> {
+    lines.get(position.line)?;
+
+    let (mut start, mut end) = line_run(lines, position.line);
+    if extent == Extent::Around {
+        if end < lines.len() {
+            end = line_run(lines, end).1;
+        } else if start > 0 && !is_blank(lines[start]) {
+            start = line_run(lines, start - 1).0;
+        }
+    }
+
+    let end = if end < lines.len() {
+        Position {
+            line: end,
+            offset: 0,
+        }
+    } else {
+        Position {
+            line: end - 1,
+            offset: lines[end - 1].graphemes(true).count(),
+        }
+    };
+
+    Some(Range::new(
+        Position {
+            line: start,
+            offset: 0,
+        },
+        end,
+    ))
+}
+
+fn sentence(lines: &[&str], position: Position, extent: Extent) -> Option<Range> {
+    if is_blank(lines.get(position.line)?) {
+        return None;
+    }
+
+    // Flatten the paragraph's graphemes, joining its lines with newlines.
+    let (first_line, end_line) = line_run(lines, position.line);
+    let mut graphemes = Vec::new();
+    for (line, content) in lines.iter().enumerate().take(end_line).skip(first_line) {
+        let mut offset = 0;
+        for grapheme in content.graphemes(true) {
+            graphemes.push((Position { line, offset }, grapheme));
+            offset += 1;
+        }
+        if line + 1 < end_line {
+            graphemes.push((Position { line, offset }, "\n"));
+        }
+    }
+    let index = graphemes.iter().position(|&(p, _)| p == position)?;
+    let length = graphemes.len();
+    let is_space = |i: usize| is_whitespace(graphemes[i].1);
+
+    // Split the paragraph into (start, end, next start) sentence bounds,
+    // where the end excludes any whitespace preceding the next sentence.
+    let mut sentences = Vec::new();
+    let mut start = (0..length).find(|&i| !is_space(i)).unwrap_or(length);
+    let mut i = start;
+    while i < length {
+        if [".", "!", "?"].contains(&graphemes[i].1) {
+            let mut end = i + 1;
+            while end < length && [")", "]", "\"", "'"].contains(&graphemes[end].1) {
+                end += 1;
+            }
+            if end == length || is_space(end) {
+                let next = (end..length).find(|&n| !is_space(n)).unwrap_or(length);
+                sentences.push((start, end, next));
+                start = next;
+                i = next;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    if start < length {
+        let end = (start..length)
+            .rev()
+            .find(|&e| !is_space(e))
+            .map_or(start, |e| e + 1);
+        sentences.push((start, end, length));
+    }
+
+    let current = sentences.iter().position(|&(_, _, next)| index < next)?;
+    let (mut start, end, next) = sentences[current];
+    let end = match extent {
+        Extent::Inside => end,
+        Extent::Around if next > end => next,
+        Extent::Around => {
+            if current > 0 {
+                start = sentences[current - 1].1;
+            }
+            end
+        }
+    };
+
+    let to_position = |i: usize| {
+        if i < length {
+            graphemes[i].0
+        } else {
+            Position {
+                line: end_line - 1,
+                offset: lines[end_line - 1].graphemes(true).count(),
+            }
+        }
+    };
+
+    Some(Range::new(to_position(start), to_position(end)))
+}
+
+// Finds the indices of the innermost code bracket pair of the specified
+// type enclosing the position, including pairs with a bracket at it.
+fn enclosing_bracket(
+    delimiters: &[Delimiter],
+    position: Position,
+    bracket: char,
+) -> Option<(usize, usize)> {
+    let bracket = bracket.to_string();
+    let &(open, close) = BRACKETS
+        .iter()
+        .find(|&&(open, close)| bracket == open || bracket == close)?;
+
+    // Walk backwards from the position to the first unmatched opening bracket.
+    let mut depth = 0;
+    for index in (0..delimiters.len()).rev() {
+        let delimiter = &delimiters[index];
+        if !delimiter.is_code() {
+            continue;
+        }
+
+        if delimiter.value == close {
+            if delimiter.position == position {
+                let open = matching_delimiter(delimiters, index)?;
+                return Some((open, index));
+            } else if delimiter.position < position {
+                depth += 1;
+            }
+        } else if delimiter.value == open && delimiter.position <= position {
+            if depth == 0 {
+                let close = matching_delimiter(delimiters, index)?;
+                return Some((index, close));
+            }
+            depth -= 1;
+        }
+    }
+
+    None
+}
+
+// Finds the indices of the innermost string delimited by the
+// specified quote character enclosing the position.
+fn enclosing_string(
+    delimiters: &[Delimiter],
+    position: Position,
+    quote: char,
+) -> Option<(usize, usize)> {
+    let quote = quote.to_string();
+
+    (0..delimiters.len())
+        .rev()
+        .filter(|&i| {
+            delimiters[i].value == quote
+                && delimiters[i].position <= position
+                && delimiters[i].is_string_punctuation("begin")
+        })
+        .filter_map(|open| Some((open, matching_delimiter(delimiters, open)?)))
+        .find(|&(_, close)| position <= delimiters[close].position)
+}
+
+fn pair_range(open: &Delimiter, close: &Delimiter, extent: Extent) -> Range {
+    match extent {
+        Extent::Inside => Range::new(
+            Position {
+                line: open.position.line,
+                offset: open.position.offset + 1,
+            },
+            close.position,
+        ),
+        Extent::Around => Range::new(
+            open.position,
+            Position {
+                line: close.position.line,
+                offset: close.position.offset + 1,
+            },
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Extent, TextObject};
+    use crate::buffer::{Buffer, Position, Range};
+    use syntect::parsing::SyntaxSet;
+
+    fn object(
+        content: &str,
+        position: (usize, usize),
+        object: TextObject,
+        extent: Extent,
+    ) -> Option<((usize, usize), (usize, usize))> {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert(content);
+        let position = Position {
+            line: position.0,
+            offset: position.1,
+        };
+
+        buffer
+            .text_object(position, object, extent, &syntax_set)
+            .unwrap()
+            .map(|range: Range| {
+                (
+                    (range.start().line, range.start().offset),
+                    (range.end().line, range.end().offset),
+                )
+            })
+    }
+
+    #[test]
+    fn word_objects_distinguish_punctuation() {
+        let content = "let x = scribe::library;";
+
+        assert_eq!(
+            object(content, (0, 10), TextObject::Word, Extent::Inside),
+            Some(((0, 8), (0, 14)))
+        );
+        assert_eq!(
+            object(content, (0, 10), TextObject::BigWord, Extent::Inside),
+            Some(((0, 8), (0, 24)))
+        );
+        assert_eq!(
+            object(content, (0, 14), TextObject::Word, Extent::Inside),
+            Some(((0, 14), (0, 16)))
+        );
+    }
+
+    #[test]
+    fn around_word_includes_leading_whitespace_at_end_of_line() {
+        let content = "scribe library";
+
+        assert_eq!(
+            object(content, (0, 10), TextObject::Word, Extent::Around),
+            Some(((0, 6), (0, 14)))
+        );
+        assert_eq!(
+            object(content, (0, 6), TextObject::Word, Extent::Around),
+            Some(((0, 6), (0, 14)))
+        );
+        assert_eq!(object("", (0, 0), TextObject::Word, Extent::Inside), None);
+    }
+
+    #[test]
+    fn sentence_objects_span_lines_within_paragraphs() {
+        let content = "// Scribe is (a library.) It\n// edits text!\n\nNext.";
+
+        assert_eq!(
+            object(content, (1, 5), TextObject::Sentence, Extent::Inside),
+            Some(((0, 26), (1, 14)))
+        );
+        assert_eq!(
+            object(content, (0, 5), TextObject::Sentence, Extent::Around),
+            Some(((0, 0), (0, 26)))
+        );
+        assert_eq!(
+            object(content, (1, 5), TextObject::Sentence, Extent::Around),
+            Some(((0, 25), (1, 14)))
+        );
+        assert_eq!(
+            object(content, (2, 0), TextObject::Sentence, Extent::Inside),
+            None
+        );
+    }
+
+    #[test]
+    fn paragraph_objects_include_following_blank_lines() {
+        let content = "scribe\nlibrary\n\n\neditor";
+
+        assert_eq!(
+            object(content, (1, 0), TextObject::Paragraph, Extent::Inside),
+            Some(((0, 0), (2, 0)))
+        );
+        assert_eq!(
+            object(content, (1, 0), TextObject::Paragraph, Extent::Around),
+            Some(((0, 0), (4, 0)))
+        );
+        assert_eq!(
+            object(content, (4, 0), TextObject::Paragraph, Extent::Around),
+            Some(((2, 0), (4, 6)))
+        );
+    }
+
+    #[test]
+    fn bracket_objects_find_the_innermost_enclosing_pair() {
+        let content = "f(a, g(\")\"), {\n  b\n})";
+
+        assert_eq!(
+            object(content, (0, 3), TextObject::Bracket('('), Extent::Inside),
+            Some(((0, 2), (2, 1)))
+        );
+        assert_eq!(
+            object(content, (0, 8), TextObject::Bracket(')'), Extent::Around),
+            Some(((0, 6), (0, 11)))
+        );
+        assert_eq!(
+            object(content, (2, 1), TextObject::Bracket('('), Extent::Around),
+            Some(((0, 1), (2, 2)))
+        );
+        assert_eq!(
+            object(content, (1, 2), TextObject::Bracket('{'), Extent::Inside),
+            Some(((0, 14), (2, 0)))
+        );
+        assert_eq!(
+            object(content, (0, 0), TextObject::Bracket('['), Extent::Inside),
+            None
+        );
+    }
+
+    #[test]
+    fn quote_objects_require_an_enclosing_string() {
+        let content = "f(\"scribe\", 'l', x)";
+
+        assert_eq!(
+            object(content, (0, 2), TextObject::Quote('"'), Extent::Around),
+            Some(((0, 2), (0, 10)))
+        );
+        assert_eq!(
+            object(content, (0, 5), TextObject::Quote('"'), Extent::Inside),
+            Some(((0, 3), (0, 9)))
+        );
+        assert_eq!(
+            object(content, (0, 17), TextObject::Quote('"'), Extent::Inside),
+            None
+        );
+    }
+
+    #[test]
+    fn scope_objects_cover_the_innermost_scope() {
+        let content = "let s = \"scribe\\n library\";";
+
+        assert_eq!(
+            object(content, (0, 10), TextObject::Scope, Extent::Around),
+            Some(((0, 8), (0, 26)))
+        );
+        assert_eq!(
+            object(content, (0, 10), TextObject::Scope, Extent::Inside),
+            Some(((0, 9), (0, 25)))
+        );
+        assert_eq!(
+            object(content, (0, 15), TextObject::Scope, Extent::Inside),
+            Some(((0, 15), (0, 17)))
+        );
+    }
+}
@@ -0,0 +1,514 @@
+use crate::buffer::{Buffer, LineRange, Position, Range};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Case conversions supported by `Buffer::change_case`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Case {
+    Upper,
+    Lower,
+
+    /// Capitalizes the first letter of each word, lowercasing the rest.
+    Title,
+
+    /// Swaps the case of each letter.
+    Toggle,
+
+    /// Joins identifier parts with underscores (e.g. `scribe_library`).
+    Snake,
+
+    /// Joins identifier parts by capitalizing them (e.g. `scribeLibrary`).
+    Camel,
+
+    /// Joins identifier parts with hyphens (e.g. `scribe-library`).
+    Kebab,
+}
+
+impl Buffer {
+    /// Converts the case of the range's content, as a single undoable
+    /// operation. Identifier conversions (`Snake`, `Camel`, and `Kebab`)
+    /// split identifiers on underscores, hyphens, and case changes, leaving
+    /// leading and trailing underscores/hyphens intact. The cursor position
+    /// is retained, if possible. Does nothing if the range is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::{Case, Position, Range};
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("let scribeLibrary = HTTPServer::new();");
+    /// let range = Range::new(
+    ///     Position{ line: 0, offset: 0 },
+    ///     Position{ line: 0, offset: 30 }
+    /// );
+    ///
+    /// buffer.change_case(&range, Case::Snake);
+    /// assert_eq!(buffer.data(), "let scribe_library = http_server::new();");
+    ///
+    /// let range = Range::new(
+    ///     Position{ line: 0, offset: 0 },
+    ///     Position{ line: 0, offset: 18 }
+    /// );
+    /// buffer.change_case(&range, Case::Upper);
+    /// assert_eq!(buffer.data(), "LET SCRIBE_LIBRARY = http_server::new();");
+    /// ```
+    pub fn change_case(&mut self, range: &Range, case: Case) {
+        let content = match self.read(range) {
+            Some(content) => content,
+            None => return,
+        };
+
+        let converted = match case {
+            Case::Upper => content.to_uppercase(),
+            Case::Lower => content.to_lowercase(),
+            Case::Title => title_case(&content),
+            Case::Toggle => content
+                .chars()
+                .map(|c| {
+                    if c.is_uppercase() {
+                        c.to_lowercase().collect::<String>()
+                    } else {
+                        c.to_uppercase().collect()
+                    }
+                })
+                .collect(),
+            Case::Snake => convert_identifiers(&content, |parts| lowercase_join(parts, "_")),
+            Case::Kebab => convert_identifiers(&content, |parts| lowercase_join(parts, "-")),
+            Case::Camel => convert_identifiers(&content, camel_case),
+        };
+
+        self.replace_range_with(range, &content, &converted);
+    }
+
+    /// Swaps the grapheme before the cursor with the one at the cursor,
+    /// advancing the cursor past both. At the end of a line, the two graphemes
+    /// preceding the cursor are swapped instead. Returns false if there
+    /// aren't two graphemes to swap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::Position;
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("scirbe");
+    /// buffer.cursor.move_to(Position{ line: 0, offset: 3 });
+    ///
+    /// assert!(buffer.transpose_graphemes());
+    /// assert_eq!(buffer.data(), "scribe");
+    /// assert_eq!(*buffer.cursor, Position{ line: 0, offset: 4 });
+    /// ```
+    pub fn transpose_graphemes(&mut self) -> bool {
+        let position = self.cursor.position;
+        let length = self.line_length(position.line).unwrap_or(0);
+        let offset = if position.offset >= length {
+            length
+        } else {
+            position.offset + 1
+        };
+        if offset < 2 {
+            return false;
+        }
+
+        let range = Range::new(
+            Position {
+                line: position.line,
+                offset: offset - 2,
+            },
+            Position {
+                line: position.line,
+                offset,
+            },
+        );
+        let content = match self.read(&range) {
+            Some(content) => content,
+            None => return false,
+        };
+        let transposed: String = content.graphemes(true).rev().collect();
+
+        self.replace_range_with(&range, &content, &transposed);
+        self.cursor.move_to(range.end());
+        true
+    }
+
+    /// Swaps the word at or before the cursor with the one that follows it on
+    /// the same line, moving the cursor to the end of the latter. Returns false
+    /// if there aren't two words to swap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::Position;
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("library, scribe");
+    /// buffer.cursor.move_to(Position{ line: 0, offset: 3 });
+    ///
+    /// assert!(buffer.transpose_words());
+    /// assert_eq!(buffer.data(), "scribe, library");
+    /// assert_eq!(*buffer.cursor, Position{ line: 0, offset: 15 });
+    /// ```
+    pub fn transpose_words(&mut self) -> bool {
+        let position = self.cursor.position;
+        let line = match self.data().split('\n').nth(position.line) {
+            Some(line) => line.to_string(),
+            None => return false,
+        };
+
+        // Find word boundaries, as grapheme offsets.
+        let mut words = Vec::new();
+        let mut offset = 0;
+        for segment in line.split_word_bounds() {
+            let length = segment.graphemes(true).count();
+            if segment.chars().any(char::is_alphanumeric) {
+                words.push((offset, offset + length, segment));
+            }
+            offset += length;
+        }
+
+        // Use the word containing or preceding the cursor, or failing
+        // that, the first word, along with the word that follows it.
+        let first = words
+            .iter()
+            .rposition(|&(start, _, _)| start <= position.offset)
+            .unwrap_or(0);
+        let first = if first + 1 == words.len() && first > 0 {
+            first - 1
+        } else {
+            first
+        };
+        let (first, second) = match (words.get(first), words.get(first + 1)) {
+            (Some(&first), Some(&second)) => (first, second),
+            _ => return false,
+        };
+
+        let range = Range::new(
+            Position {
+                line: position.line,
+                offset: first.0,
+            },
+            Position {
+                line: position.line,
+                offset: second.1,
+            },
+        );
+        let separator: String = line
+            .graphemes(true)
+            .skip(first.1)
+            .take(second.0 - first.1)
+            .collect();
+        let content = format!("{}{}{}", first.2, separator, second.2);
+        let transposed = format!("{}{}{}", second.2, separator, first.2);
+
+        self.replace_range_with(&range, &content, &transposed);
+        self.cursor.move_to(range.end());
+        true
+    }
+
+    /// Swaps the cursor's line with the one above it, moving the cursor to
+    /// the start of the line below them. Returns false on the first line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Buffer;
+    /// use scribe::buffer::Position;
+    ///
+    /// let mut buffer = Buffer::new();
+    /// buffer.insert("library\nscribe\neditor");
+    /// buffer.cursor.move_to(Position{ line: 1, offset: 2 });
+    ///
+    /// assert!(buffer.transpose_lines());
+    /// assert_eq!(buffer.data(), "scribe\nlibrary\neditor");
+    /// assert_eq!(*buffer.cursor, Position{ line: 2, offset: 0 });
+    /// ```
+    pub fn transpose_lines(&mut self) -> bool {
+        let line = self.cursor.line;
+        if !self.move_lines_up(&LineRange::new(line, line + 1)) {
+            return false;
+        }
+
+        if !self.cursor.move_to(Position {
+            line: line + 1,
+            offset: 0,
+        }) {
+            self.cursor.move_to(Position { line, offset: 0 });
+            self.cursor.move_to_end_of_line();
+        }
+        true
+    }
+
+    // Replaces the range's content as a single operation,
+    // retaining the cursor position if it's still valid.
+    fn replace_range_with(&mut self, range: &Range, content: &str, replacement: &str) {
+        if content == replacement {
+            return;
+        }
+
+        let cursor = self.cursor.position;
+        self.in_operation_group(|buffer| {
+            buffer.replace_range_minimally(range, content, replacement)
+        });
+
+        if !self.cursor.move_to(cursor) {
+            self.cursor.move_to(Position {
+                line: cursor.line,
+                offset: 0,
+            });
+            self.cursor.move_to_end_of_line();
+        }
+    }
+}
+
+fn title_case(content: &str) -> String {
+    content
+        .split_word_bounds()
+        .map(|word| {
+            let mut graphemes = word.graphemes(true);
+            match graphemes.next() {
+                Some(first) => format!(
+                    "{}{}",
+                    first.to_uppercase(),
+                    graphemes.as_str().to_lowercase()
+                ),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+// Applies the conversion to each identifier in the content, passing it the
+// identifier's parts and preserving any leading or trailing separators.
+fn convert_identifiers<F: Fn(&[String]) -> String>(content: &str, convert: F) -> String {
+    let is_identifier = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    let is_separator = |c: char| c == '_' || c == '-';
+    let mut converted = String::new();
+    let mut remaining = content;
+
+    while let Some(start) = remaining.find(is_identifier) {
+        converted.push_str(&remaining[..start]);
+        remaining = &remaining[start..];
+        let end = remaining
+            .find(|c: char| !is_identifier(c))
+            .unwrap_or(remaining.len());
+        let identifier = &remaining[..end];
+        remaining = &remaining[end..];
+
+        let core = identifier.trim_matches(is_separator);
+        if core.is_empty() {
+            converted.push_str(identifier);
+            continue;
+        }
+        let prefix =
+            &identifier[..identifier.len() - identifier.trim_start_matches(is_separator).len()];
+        let suffix = &identifier[identifier.trim_end_matches(is_separator).len()..];
+
+        converted.push_str(prefix);
+        converted.push_str(&convert(&identifier_parts(core)));
+        converted.push_str(suffix);
+    }
+    converted.push_str(remaining);
+
+    converted
+}
+
+// Splits an identifier into parts on separators and case changes,
+// keeping acronyms together (e.g. "HTTPServer" becomes "HTTP", "Server").
+fn identifier_parts(identifier: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    for segment in identifier.split(['_', '-']).filter(|s| !s.is_empty()) {
+        let chars: Vec<char> = segment.chars().collect();
+        let mut part = String::new();
+
+        for (index, &c) in chars.iter().enumerate() {
+            let previous = index.checked_sub(1).map(|i| chars[i]);
+            let next = chars.get(index + 1);
+            let boundary = c.is_uppercase()
+                && previous.is_some_and(|p| {
+                    p.is_lowercase()
+                        || p.is_numeric()
+                        || (p.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+                });
+
+            if boundary && !part.is_empty() {
+                parts.push(std::mem::take(&mut part));
+            }
+            part.push(c);
+        }
+        parts.push(part);
+    }
+
+    parts
+}
+
+fn lowercase_join(parts: &[String], separator: &str) -> String {
+    parts
+        .iter()
+        .map(|part| part.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn camel_case(parts: &[String]) -> String {
+    parts
+        .iter()
+        .enumerate()
+        .map(|(index, part)| {
+            let part = part.to_lowercase();
+            if index == 0 {
+                return part;
+            }
+
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => part,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{identifier_parts, Case};
+    use crate::buffer::{Buffer, Position, Range};
+
+    fn convert(content: &str, case: Case) -> String {
+        let mut buffer = Buffer::new();
+        buffer.insert(content);
+        let end = buffer.end_position();
+        buffer.change_case(&Range::new(Position::new(), end), case);
+
+        buffer.data()
+    }
+
+    #[test]
+    fn identifier_parts_splits_on_separators_and_case_changes() {
+        assert_eq!(
+            identifier_parts("parseHTTPResponse_v2-final"),
+            vec!["parse", "HTTP", "Response", "v2", "final"]
+        );
+        assert_eq!(identifier_parts("Version2Beta"), vec!["Version2", "Beta"]);
+    }
+
+    #[test]
+    fn change_case_converts_identifiers() {
+        assert_eq!(convert("scribe_library", Case::Camel), "scribeLibrary");
+        assert_eq!(convert("ScribeLibrary", Case::Kebab), "scribe-library");
+        assert_eq!(convert("scribe-library", Case::Snake), "scribe_library");
+        assert_eq!(convert("__scribe_lib__", Case::Camel), "__scribeLib__");
+        assert_eq!(convert("a - b", Case::Snake), "a - b");
+    }
+
+    #[test]
+    fn change_case_handles_title_and_toggle_case() {
+        assert_eq!(
+            convert("the SCRIBE library", Case::Title),
+            "The Scribe Library"
+        );
+        assert_eq!(convert("Scribe ÉDITOR", Case::Toggle), "sCRIBE éditor");
+        assert_eq!(convert("straße", Case::Upper), "STRASSE");
+    }
+
+    #[test]
+    fn change_case_is_undone_as_a_single_operation() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary");
+        buffer.cursor.move_to(Position { line: 1, offset: 2 });
+        buffer.change_case(
+            &Range::new(Position::new(), Position { line: 1, offset: 7 }),
+            Case::Upper,
+        );
+
+        assert_eq!(buffer.data(), "SCRIBE\nLIBRARY");
+        assert_eq!(*buffer.cursor, Position { line: 1, offset: 2 });
+
+        buffer.undo();
+        assert_eq!(buffer.data(), "scribe\nlibrary");
+    }
+
+    #[test]
+    fn change_case_ignores_invalid_ranges() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe");
+        buffer.change_case(
+            &Range::new(Position::new(), Position { line: 1, offset: 0 }),
+            Case::Upper,
+        );
+
+        assert_eq!(buffer.data(), "scribe");
+    }
+
+    #[test]
+    fn transpose_graphemes_swaps_preceding_graphemes_at_end_of_line() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribé\nlibrary");
+        buffer.cursor.move_to(Position { line: 0, offset: 6 });
+
+        assert!(buffer.transpose_graphemes());
+        assert_eq!(buffer.data(), "scriéb\nlibrary");
+        assert_eq!(*buffer.cursor, Position { line: 0, offset: 6 });
+    }
+
+    #[test]
+    fn transpose_graphemes_requires_two_graphemes() {
+        let mut buffer = Buffer::new();
+        buffer.insert("s\nlibrary");
+
+        assert!(!buffer.transpose_graphemes());
+        buffer.cursor.move_to(Position { line: 0, offset: 1 });
+        assert!(!buffer.transpose_graphemes());
+        assert_eq!(buffer.data(), "s\nlibrary");
+    }
+
+    #[test]
+    fn transpose_words_uses_preceding_words_at_end_of_line() {
+        let mut buffer = Buffer::new();
+        buffer.insert("use scribe library;");
+        buffer.cursor.move_to(Position {
+            line: 0,
+            offset: 19,
+        });
+
+        assert!(buffer.transpose_words());
+        assert_eq!(buffer.data(), "use library scribe;");
+        assert_eq!(
+            *buffer.cursor,
+            Position {
+                line: 0,
+                offset: 18
+            }
+        );
+    }
+
+    #[test]
+    fn transpose_words_requires_two_words() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe;");
+
+        assert!(!buffer.transpose_words());
+    }
+
+    #[test]
+    fn transpose_lines_does_nothing_on_first_line() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary");
+
+        assert!(!buffer.transpose_lines());
+        assert_eq!(buffer.data(), "scribe\nlibrary");
+    }
+
+    #[test]
+    fn transpose_lines_moves_cursor_to_end_of_last_line() {
+        let mut buffer = Buffer::new();
+        buffer.insert("scribe\nlibrary");
+        buffer.cursor.move_to(Position { line: 1, offset: 0 });
+
+        assert!(buffer.transpose_lines());
+        assert_eq!(buffer.data(), "library\nscribe");
+        assert_eq!(*buffer.cursor, Position { line: 1, offset: 6 });
+    }
+}
@@ -1,10 +1,13 @@
+mod parse_cache;
 mod token_iterator;
 mod token_set;
 
+pub(crate) use self::parse_cache::ParseCache;
 pub use self::token_iterator::TokenIterator;
 pub use self::token_set::TokenSet;
 
 use crate::buffer::Position;
+use crate::errors::Error;
 use syntect::parsing::ScopeStack;
 
 #[derive(Debug, PartialEq)]
@@ -19,3 +22,13 @@ pub struct Lexeme<'a> {
     pub scope: ScopeStack,
     pub position: Position,
 }
+
+/// A line that couldn't be parsed. Rather than halting tokenization, the
+/// line's content is produced as a single lexeme (using the scope in effect
+/// at its start), and parsing resumes on the next line from the state that
+/// preceded the failure.
+#[derive(Debug)]
+pub struct TokenError {
+    pub line: usize,
+    pub error: Error,
+}
@@ -0,0 +1,271 @@
+use std::cmp;
+use std::iter;
+use syntect::parsing::{ParseState, Scope, ScopeStack, ScopeStackOp, SyntaxReference};
+
+// The parser state at the start of a line, and the events produced by
+// parsing the line from that state. Lines that failed to parse have no
+// events, and are always re-parsed, so that their errors are reported.
+#[derive(Clone)]
+struct Checkpoint {
+    parser: ParseState,
+    scopes: ScopeStack,
+    events: Option<Vec<(usize, ScopeStackOp)>>,
+    failed: bool,
+}
+
+/// Per-line parse checkpoints for a buffer, allowing its content to be
+/// re-tokenized without re-parsing lines that haven't been affected by
+/// changes since they were last parsed.
+///
+/// Checkpoints before `valid` are known to be current. Edits invalidate
+/// checkpoints from the changed line onward, but later ones are kept (and
+/// shifted to account for added or removed lines) as hints: once parsing
+/// moves past the last changed line and produces a state matching a hint,
+/// the parser has converged, and the remaining checkpoints are current.
+#[derive(Default)]
+pub(crate) struct ParseCache {
+    syntax: Option<Scope>,
+    checkpoints: Vec<Option<Checkpoint>>,
+    valid: usize,
+    last_changed_line: Option<usize>,
+}
+
+impl ParseCache {
+    // Discards all checkpoints if they were produced using another syntax.
+    pub(crate) fn prepare(&mut self, syntax: &SyntaxReference) {
+        if self.syntax != Some(syntax.scope) {
+            self.clear();
+            self.syntax = Some(syntax.scope);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        *self = ParseCache::default();
+    }
+
+    // Accounts for an edit starting on the specified line that replaced
+    // `removed` subsequent lines with `added` new ones.
+    pub(crate) fn invalidate(&mut self, line: usize, removed: usize, added: usize) {
+        if line >= self.checkpoints.len() {
+            return;
+        }
+
+        // The line's starting state is unaffected, but its events are not.
+        self.valid = cmp::min(self.valid, line);
+        if let Some(checkpoint) = &mut self.checkpoints[line] {
+            checkpoint.events = None;
+            checkpoint.failed = false;
+        }
+
+        let end = cmp::min(line + 1 + removed, self.checkpoints.len());
+        self.checkpoints
+            .splice(line + 1..end, iter::repeat_n(None, added));
+
+        // Track the last changed line, accounting for this edit's line delta.
+        let previous = self.last_changed_line.map_or(0, |previous| {
+            if previous > line + removed {
+                previous - removed + added
+            } else {
+                previous
+            }
+        });
+        self.last_changed_line = Some(cmp::max(previous, line + added));
+    }
+
+    // The events for the line, if it's unchanged since they were recorded,
+    // and the state following it is known.
+    pub(crate) fn events(&self, line: usize) -> Option<&[(usize, ScopeStackOp)]> {
+        if line >= self.valid || self.checkpoints.get(line + 1).is_none() {
+            return None;
+        }
+
+        self.checkpoints[line]
+            .as_ref()
+            .filter(|checkpoint| !checkpoint.failed)
+            .and_then(|checkpoint| checkpoint.events.as_deref())
+    }
+
+    // The parser and scope state at the start of the line, if it's known.
+    pub(crate) fn state(&self, line: usize) -> Option<(&ParseState, &ScopeStack)> {
+        if line > self.valid {
+            return None;
+        }
+
+        self.checkpoints
+            .get(line)?
+            .as_ref()
+            .map(|checkpoint| (&checkpoint.parser, &checkpoint.scopes))
+    }
+
+    // The latest known state at or before the start of the line, along with
+    // the line to which it applies.
+    pub(crate) fn nearest_state(&self, line: usize) -> Option<(usize, &ParseState, &ScopeStack)> {
+        let line = cmp::min(line, self.valid);
+
+        self.state(line)
+            .map(|(parser, scopes)| (line, parser, scopes))
+    }
+
+    // Records the result of parsing the line, which must be the first one
+    // without current events. If the resulting state matches the next
+    // line's checkpoint, and all changes have been re-parsed, the remaining
+    // checkpoints are considered current.
+    pub(crate) fn store(
+        &mut self,
+        line: usize,
+        start: (ParseState, ScopeStack),
+        events: &[(usize, ScopeStackOp)],
+        end: (ParseState, ScopeStack),
+    ) {
+        self.record(line, start, events, end, false);
+    }
+
+    // Records that the line couldn't be parsed, with parsing resuming from
+    // its start state, so that the lines following it can still be cached.
+    pub(crate) fn store_failure(&mut self, line: usize, start: (ParseState, ScopeStack)) {
+        let end = start.clone();
+        self.record(line, start, &[], end, true);
+    }
+
+    fn record(
+        &mut self,
+        line: usize,
+        start: (ParseState, ScopeStack),
+        events: &[(usize, ScopeStackOp)],
+        end: (ParseState, ScopeStack),
+        failed: bool,
+    ) {
+        if line != self.valid || line > self.checkpoints.len() {
+            return;
+        }
+
+        let checkpoint = Some(Checkpoint {
+            parser: start.0,
+            scopes: start.1,
+            events: Some(events.to_vec()),
+            failed,
+        });
+        if line == self.checkpoints.len() {
+            self.checkpoints.push(checkpoint);
+        } else {
+            self.checkpoints[line] = checkpoint;
+        }
+        self.valid = line + 1;
+
+        let next = line + 1;
+        let converged = self.last_changed_line.is_none_or(|changed| next > changed)
+            && matches!(
+                self.checkpoints.get(next),
+                Some(Some(checkpoint)) if checkpoint.parser == end.0 && checkpoint.scopes == end.1
+            );
+
+        if converged {
+            self.valid = self.checkpoints[next..]
+                .iter()
+                .position(|c| c.as_ref().is_none_or(|c| c.events.is_none()))
+                .map_or(self.checkpoints.len(), |offset| next + offset);
+            self.last_changed_line = None;
+        } else {
+            let checkpoint = Some(Checkpoint {
+                parser: end.0,
+                scopes: end.1,
+                events: None,
+                failed: false,
+            });
+            if next == self.checkpoints.len() {
+                self.checkpoints.push(checkpoint);
+            } else {
+                self.checkpoints[next] = checkpoint;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParseCache;
+    use syntect::parsing::{ParseState, ScopeStack, SyntaxSet};
+
+    // Parses the lines into the cache, starting from the first invalid
+    // line, returning the number of lines that were parsed.
+    fn parse(cache: &mut ParseCache, lines: &[&str], syntax_set: &SyntaxSet) -> usize {
+        let syntax = syntax_set.find_syntax_by_extension("rs").unwrap();
+        cache.prepare(syntax);
+        let mut parsed = 0;
+
+        for (line, content) in lines.iter().enumerate() {
+            if cache.events(line).is_some() {
+                continue;
+            }
+
+            let (mut parser, mut scopes) = match cache.state(line) {
+                Some((parser, scopes)) => (parser.clone(), scopes.clone()),
+                None => (ParseState::new(syntax), ScopeStack::new()),
+            };
+            let start = (parser.clone(), scopes.clone());
+            let events = parser.parse_line(content, syntax_set).unwrap();
+            for (_, op) in events.iter() {
+                scopes.apply(op).unwrap();
+            }
+            cache.store(line, start, &events, (parser, scopes));
+            parsed += 1;
+        }
+
+        parsed
+    }
+
+    #[test]
+    fn cache_reparses_changed_lines_until_states_converge() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut cache = ParseCache::default();
+        let mut lines = vec!["fn main() {\n"; 100];
+        assert_eq!(parse(&mut cache, &lines, &syntax_set), 100);
+        assert_eq!(parse(&mut cache, &lines, &syntax_set), 0);
+
+        lines[50] = "fn scribe() {\n";
+        cache.invalidate(50, 0, 0);
+        assert_eq!(parse(&mut cache, &lines, &syntax_set), 1);
+    }
+
+    #[test]
+    fn cache_continues_parsing_while_states_differ() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut cache = ParseCache::default();
+        let mut lines = vec!["let x = 1;\n"; 10];
+        parse(&mut cache, &lines, &syntax_set);
+
+        // Opening a block comment changes the state of every following line.
+        lines[4] = "/* let x = 1;\n";
+        cache.invalidate(4, 0, 0);
+        assert_eq!(parse(&mut cache, &lines, &syntax_set), 6);
+    }
+
+    #[test]
+    fn cache_shifts_checkpoints_for_added_and_removed_lines() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut cache = ParseCache::default();
+        let mut lines = vec!["let x = 1;\n"; 10];
+        parse(&mut cache, &lines, &syntax_set);
+
+        // Insert two lines after line 2, and remove three starting at line 7.
+        lines.splice(2..3, ["let y = 2;\n"; 3]);
+        cache.invalidate(2, 0, 2);
+        lines.splice(7..10, ["let z = 3;\n"]);
+        cache.invalidate(7, 2, 0);
+
+        // Lines 2 through 7 are re-parsed, after which states converge.
+        assert_eq!(parse(&mut cache, &lines, &syntax_set), 6);
+        assert_eq!(parse(&mut cache, &lines, &syntax_set), 0);
+    }
+
+    #[test]
+    fn prepare_discards_checkpoints_for_other_syntaxes() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut cache = ParseCache::default();
+        let lines = vec!["let x = 1;\n"; 10];
+        parse(&mut cache, &lines, &syntax_set);
+
+        cache.prepare(syntax_set.find_syntax_plain_text());
+        assert!(cache.state(1).is_none());
+    }
+}
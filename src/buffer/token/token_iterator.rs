@@ -1,7 +1,10 @@
-use crate::buffer::{Lexeme, Position, Token};
+use crate::buffer::token::{ParseCache, TokenError};
+use crate::buffer::{Lexeme, LineRange, Position, Token};
 use crate::errors::*;
 use crate::util::LineIterator;
+use std::cell::{Ref, RefCell};
 use std::cmp;
+use std::rc::Rc;
 use syntect::parsing::{ParseState, ScopeStack, ScopeStackOp, SyntaxReference, SyntaxSet};
 use unicode_segmentation::UnicodeSegmentation;
 
@@ -14,6 +17,10 @@ pub struct TokenIterator<'a> {
     current_position: Position,
     line_events: Vec<(usize, ScopeStackOp)>,
     syntaxes: &'a SyntaxSet,
+    cache: Option<&'a RefCell<ParseCache>>,
+    stale_parser: bool,
+    end_line: Option<usize>,
+    line_errors: Rc<RefCell<Vec<TokenError>>>,
     pub error: Option<Error>,
 }
 
@@ -22,6 +29,44 @@ impl<'a> TokenIterator<'a> {
         data: &'a str,
         def: &'a SyntaxReference,
         syntaxes: &'a SyntaxSet,
+    ) -> Result<TokenIterator<'a>> {
+        TokenIterator::build(data, def, syntaxes, None, None, Default::default())
+    }
+
+    // Creates an iterator that re-uses events for lines that haven't changed
+    // since they were last parsed, and records the results of parsing others.
+    pub(crate) fn cached(
+        data: &'a str,
+        def: &'a SyntaxReference,
+        syntaxes: &'a SyntaxSet,
+        cache: &'a RefCell<ParseCache>,
+        lines: Option<&LineRange>,
+        line_errors: Rc<RefCell<Vec<TokenError>>>,
+    ) -> Result<TokenIterator<'a>> {
+        cache.borrow_mut().prepare(def);
+
+        TokenIterator::build(data, def, syntaxes, Some(cache), lines, line_errors)
+    }
+
+    // Creates an iterator that only produces tokens for the specified lines
+    // (or all of them), recording line errors in the provided collection.
+    pub(crate) fn for_lines(
+        data: &'a str,
+        def: &'a SyntaxReference,
+        syntaxes: &'a SyntaxSet,
+        lines: Option<&LineRange>,
+        line_errors: Rc<RefCell<Vec<TokenError>>>,
+    ) -> Result<TokenIterator<'a>> {
+        TokenIterator::build(data, def, syntaxes, None, lines, line_errors)
+    }
+
+    fn build(
+        data: &'a str,
+        def: &'a SyntaxReference,
+        syntaxes: &'a SyntaxSet,
+        cache: Option<&'a RefCell<ParseCache>>,
+        lines: Option<&LineRange>,
+        line_errors: Rc<RefCell<Vec<TokenError>>>,
     ) -> Result<TokenIterator<'a>> {
         let mut token_iterator = TokenIterator {
             scopes: ScopeStack::new(),
@@ -32,15 +77,30 @@ impl<'a> TokenIterator<'a> {
             current_position: Position { line: 0, offset: 0 },
             line_events: Vec::new(),
             syntaxes,
+            cache,
+            stale_parser: false,
+            end_line: None,
+            line_errors,
             error: None,
         };
 
+        if let Some(lines) = lines {
+            token_iterator.end_line = Some(lines.end());
+            token_iterator.skip_lines(lines.start())?;
+        }
+
         // Preload the first line
         token_iterator.parse_next_line()?;
 
         Ok(token_iterator)
     }
 
+    /// Lines that couldn't be parsed so far. Unlike `error`, which is set
+    /// when tokenization can't continue, these don't halt iteration.
+    pub fn line_errors(&self) -> Ref<'_, [TokenError]> {
+        Ref::map(self.line_errors.borrow(), |errors| errors.as_slice())
+    }
+
     fn next_token(&mut self) -> Option<Token<'a>> {
         let token_result = self.build_next_token();
 
@@ -132,11 +192,42 @@ impl<'a> TokenIterator<'a> {
         Ok(lexeme)
     }
 
+    // Advances the parser to the start of the specified line, resuming from
+    // the nearest cached checkpoint and discarding the skipped lines' tokens.
+    fn skip_lines(&mut self, line: usize) -> Result<()> {
+        let mut skipped = 0;
+        if let Some(cache) = self.cache {
+            if let Some((checkpoint, parser, scopes)) = cache.borrow().nearest_state(line) {
+                self.parser = parser.clone();
+                self.scopes = scopes.clone();
+                skipped = checkpoint;
+            }
+        }
+        self.lines.by_ref().take(skipped).for_each(drop);
+
+        for _ in skipped..line {
+            let (line_number, line) = match self.lines.next() {
+                Some(line) => line,
+                None => break,
+            };
+            for (_, scope_change) in self.line_events(line_number, line)? {
+                self.scopes.apply(&scope_change)?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn parse_next_line(&mut self) -> Result<()> {
-        if let Some((line_number, line)) = self.lines.next() {
+        let next_line = self
+            .lines
+            .next()
+            .filter(|&(line_number, _)| self.end_line.is_none_or(|end| line_number < end));
+
+        if let Some((line_number, line)) = next_line {
             // We reverse the line elements so that we can pop them off one at a
             // time, handling each event while allowing us to stop at any point.
-            let mut line_events = self.parser.parse_line(line, self.syntaxes)?;
+            let mut line_events = self.line_events(line_number, line)?;
             line_events.reverse();
             self.line_events = line_events;
 
@@ -157,6 +248,74 @@ impl<'a> TokenIterator<'a> {
 
         Ok(())
     }
+
+    // Parses the line, using cached events if they're available. Lines that
+    // can't be parsed are recorded, and produce no events.
+    fn line_events(
+        &mut self,
+        line_number: usize,
+        line: &str,
+    ) -> Result<Vec<(usize, ScopeStackOp)>> {
+        if let Some(cache) = self.cache {
+            if let Some(events) = cache.borrow().events(line_number) {
+                // The parser isn't advanced past lines with cached events.
+                self.stale_parser = true;
+
+                return Ok(events.to_vec());
+            }
+
+            if self.stale_parser {
+                match cache.borrow().state(line_number) {
+                    Some((parser, _)) => self.parser = parser.clone(),
+                    None => bail!("missing parse state for line {}", line_number),
+                }
+                self.stale_parser = false;
+            }
+        }
+
+        let start = (self.parser.clone(), self.scopes.clone());
+        match self.parse_line(line) {
+            Ok((events, scopes)) => {
+                if let Some(cache) = self.cache {
+                    cache.borrow_mut().store(
+                        line_number,
+                        start,
+                        &events,
+                        (self.parser.clone(), scopes),
+                    );
+                }
+
+                Ok(events)
+            }
+            Err(error) => {
+                // Resume from the state preceding the failure. Failed lines
+                // are re-parsed (and reported) the next time the buffer is
+                // tokenized, but the lines following them are cached.
+                if let Some(cache) = self.cache {
+                    cache.borrow_mut().store_failure(line_number, start.clone());
+                }
+                self.parser = start.0;
+                self.line_errors.borrow_mut().push(TokenError {
+                    line: line_number,
+                    error,
+                });
+
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    // Parses the line, verifying that its events can be applied
+    // to the current scopes, which are returned after doing so.
+    fn parse_line(&mut self, line: &str) -> Result<(Vec<(usize, ScopeStackOp)>, ScopeStack)> {
+        let events = self.parser.parse_line(line, self.syntaxes)?;
+        let mut scopes = self.scopes.clone();
+        for (_, scope_change) in events.iter() {
+            scopes.apply(scope_change)?;
+        }
+
+        Ok((events, scopes))
+    }
 }
 
 impl<'a> Iterator for TokenIterator<'a> {
@@ -1,11 +1,16 @@
-use crate::buffer::token::TokenIterator;
+use crate::buffer::token::{ParseCache, TokenError, TokenIterator};
+use crate::buffer::LineRange;
 use crate::errors::*;
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
 use syntect::parsing::{SyntaxReference, SyntaxSet};
 
 pub struct TokenSet<'a> {
     data: String,
     syntax_definition: &'a SyntaxReference,
     syntaxes: &'a SyntaxSet,
+    cache: Option<Rc<RefCell<ParseCache>>>,
+    line_errors: Rc<RefCell<Vec<TokenError>>>,
 }
 
 impl<'a> TokenSet<'a> {
@@ -14,10 +19,216 @@ impl<'a> TokenSet<'a> {
             data,
             syntax_definition: def,
             syntaxes,
+            cache: None,
+            line_errors: Default::default(),
         }
     }
 
-    pub fn iter(&self) -> Result<TokenIterator> {
-        TokenIterator::new(&self.data, self.syntax_definition, self.syntaxes)
+    // Uses the cache to avoid re-parsing unchanged lines.
+    pub(crate) fn with_cache(mut self, cache: Rc<RefCell<ParseCache>>) -> TokenSet<'a> {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn iter(&self) -> Result<TokenIterator<'_>> {
+        let line_errors = self.reset_line_errors();
+        match self.cache {
+            Some(ref cache) => TokenIterator::cached(
+                &self.data,
+                self.syntax_definition,
+                self.syntaxes,
+                cache,
+                None,
+                line_errors,
+            ),
+            None => TokenIterator::for_lines(
+                &self.data,
+                self.syntax_definition,
+                self.syntaxes,
+                None,
+                line_errors,
+            ),
+        }
+    }
+
+    /// Iterates over the tokens for the specified lines (excluding the
+    /// range's end line). Sets produced by a `Workspace` resume parsing from
+    /// the nearest cached state, rather than the start of the buffer.
+    pub fn iter_lines(&self, lines: &LineRange) -> Result<TokenIterator<'_>> {
+        let line_errors = self.reset_line_errors();
+        match self.cache {
+            Some(ref cache) => TokenIterator::cached(
+                &self.data,
+                self.syntax_definition,
+                self.syntaxes,
+                cache,
+                Some(lines),
+                line_errors,
+            ),
+            None => TokenIterator::for_lines(
+                &self.data,
+                self.syntax_definition,
+                self.syntaxes,
+                Some(lines),
+                line_errors,
+            ),
+        }
+    }
+
+    /// Lines that couldn't be parsed by the most recently created iterator
+    /// (so far), including any parsed to reach the start of its line range.
+    /// Their content is produced as plain lexemes; see `TokenError`.
+    pub fn line_errors(&self) -> Ref<'_, [TokenError]> {
+        Ref::map(self.line_errors.borrow(), |errors| errors.as_slice())
+    }
+
+    // Clears errors from previous iterators, returning
+    // a reference for the next one to record its own.
+    fn reset_line_errors(&self) -> Rc<RefCell<Vec<TokenError>>> {
+        self.line_errors.borrow_mut().clear();
+        self.line_errors.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokenSet;
+    use crate::buffer::{Buffer, Lexeme, LineRange, Position, Token};
+    use syntect::parsing::{SyntaxDefinition, SyntaxSet, SyntaxSetBuilder};
+
+    // The lexemes from the tokens, excluding any on lines outside of the range.
+    fn lexemes<'a, T: Iterator<Item = Token<'a>>>(tokens: T, lines: &LineRange) -> Vec<Lexeme<'a>> {
+        tokens
+            .filter_map(|token| match token {
+                Token::Lexeme(lexeme) if lines.includes(lexeme.position.line) => Some(lexeme),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn iter_lines_only_produces_tokens_for_the_range() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let def = syntax_set.find_syntax_by_extension("rs").unwrap();
+        let data = "/*\n*/\nfn main() {\n    scribe();\n}\n".repeat(10);
+        let tokens = TokenSet::new(data, def, &syntax_set);
+        let lines = LineRange::new(21, 23);
+
+        let range_tokens: Vec<Token> = tokens.iter_lines(&lines).unwrap().collect();
+        assert_eq!(
+            range_tokens
+                .iter()
+                .filter(|t| **t == Token::Newline)
+                .count(),
+            1
+        );
+        assert_eq!(
+            lexemes(range_tokens.into_iter(), &lines),
+            lexemes(tokens.iter().unwrap(), &lines)
+        );
+    }
+
+    #[test]
+    fn iter_lines_resumes_from_cached_state() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert("/*\n*/\nfn main() {\n    scribe();\n}\n".repeat(10));
+        let lines = LineRange::new(21, 23);
+        buffer
+            .tokens(&syntax_set)
+            .iter_lines(&LineRange::new(10, 12))
+            .unwrap()
+            .for_each(drop);
+
+        // Modify a line before the range, after the cached state.
+        buffer.cursor.move_to(Position {
+            line: 15,
+            offset: 0,
+        });
+        buffer.insert("/* ");
+
+        let tokens = buffer.tokens(&syntax_set);
+        let uncached = TokenSet::new(
+            buffer.data(),
+            buffer.syntax_definition.as_ref().unwrap(),
+            &syntax_set,
+        );
+        assert_eq!(
+            lexemes(tokens.iter_lines(&lines).unwrap(), &lines),
+            lexemes(uncached.iter().unwrap(), &lines)
+        );
+        assert_eq!(
+            lexemes(tokens.iter_lines(&lines).unwrap(), &lines),
+            lexemes(uncached.iter().unwrap(), &lines)
+        );
+    }
+
+    // A syntax set whose only syntax fails to parse lines containing "embed",
+    // since it pushes a context from a syntax that isn't in the set.
+    fn broken_syntax_set() -> SyntaxSet {
+        let definition = SyntaxDefinition::load_from_str(
+            "name: Broken\n\
+             scope: source.broken\n\
+             contexts:\n  \
+               main:\n    \
+                 - match: embed\n      \
+                   push: scope:source.missing\n",
+            true,
+            None,
+        )
+        .unwrap();
+        let mut builder = SyntaxSetBuilder::new();
+        builder.add(definition);
+
+        builder.build()
+    }
+
+    #[test]
+    fn iterators_record_line_errors_and_continue() {
+        let syntax_set = broken_syntax_set();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_name("Broken").cloned();
+        buffer.insert("fine\nembed this\nafter");
+        let tokens = buffer.tokens(&syntax_set);
+
+        let mut iterator = tokens.iter().unwrap();
+        let values: Vec<&str> = iterator
+            .by_ref()
+            .filter_map(|token| match token {
+                Token::Lexeme(lexeme) => Some(lexeme.value),
+                Token::Newline => None,
+            })
+            .collect();
+        assert_eq!(values, vec!["fine", "embed this", "after"]);
+        assert!(iterator.error.is_none());
+        assert_eq!(iterator.line_errors().len(), 1);
+        assert_eq!(tokens.line_errors()[0].line, 1);
+
+        // Failed lines aren't cached, so they're reported on every iteration,
+        // while errors from previous iterations are discarded.
+        tokens.iter().unwrap().for_each(drop);
+        assert_eq!(tokens.line_errors().len(), 1);
+        tokens
+            .iter_lines(&LineRange::new(0, 1))
+            .unwrap()
+            .for_each(drop);
+        assert!(tokens.line_errors().is_empty());
+    }
+
+    #[test]
+    fn lines_following_line_errors_are_cached() {
+        let syntax_set = broken_syntax_set();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_name("Broken").cloned();
+        buffer.insert("fine\nembed this\nafter\nend");
+        buffer.tokens(&syntax_set).iter().unwrap().for_each(drop);
+
+        // Only the failed line will be re-parsed by subsequent iterations.
+        let cache = buffer.parse_cache.borrow();
+        assert!(cache.events(0).is_some());
+        assert!(cache.events(1).is_none());
+        assert!(cache.events(2).is_some());
+        assert!(cache.events(3).is_some());
     }
 }
@@ -0,0 +1,486 @@
+//! EditorConfig (https://editorconfig.org) file resolution.
+
+use crate::buffer::{Buffer, IndentStyle, LineEnding};
+use crate::errors::*;
+use std::fs;
+use std::path::Path;
+
+const FILE_NAME: &str = ".editorconfig";
+
+/// The EditorConfig properties that apply to a file. Properties that
+/// aren't defined, or are explicitly `unset`, are left as `None`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EditorConfig {
+    pub indent_style: Option<IndentStyle>,
+    pub indent_size: Option<IndentSize>,
+    pub tab_width: Option<usize>,
+    pub end_of_line: Option<LineEnding>,
+    pub charset: Option<String>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+}
+
+/// The value of an `indent_size` property.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IndentSize {
+    Columns(usize),
+
+    /// Use the `tab_width` value.
+    Tab,
+}
+
+impl EditorConfig {
+    /// Resolves the properties for the file at the specified path, reading
+    /// `.editorconfig` files from its directory up to (and including) the
+    /// `root` directory, or one declaring `root = true`, whichever comes
+    /// first. Files closer to the path take precedence, and those that can't
+    /// be read (or aren't valid UTF-8) are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::EditorConfig;
+    /// use scribe::buffer::IndentStyle;
+    /// use std::path::Path;
+    ///
+    /// let config = EditorConfig::resolve(
+    ///     Path::new("tests/sample/editorconfig/nested/file.rs"),
+    ///     Path::new("tests/sample/editorconfig")
+    /// ).unwrap();
+    ///
+    /// assert_eq!(config.indent_style, Some(IndentStyle::Tabs));
+    /// assert_eq!(config.trim_trailing_whitespace, Some(true));
+    /// ```
+    pub fn resolve(path: &Path, root: &Path) -> Result<EditorConfig> {
+        let path = path.canonicalize()?;
+        let root = root.canonicalize()?;
+
+        // Collect applicable files, starting with the closest.
+        let mut files = Vec::new();
+        for directory in path.ancestors().skip(1) {
+            let config_path = directory.join(FILE_NAME);
+            // Files are advisory, so those that can't be read are skipped.
+            if let Ok(content) = fs::read_to_string(&config_path) {
+                let is_root = parse(&content).0;
+                files.push((directory.to_path_buf(), content));
+
+                if is_root {
+                    break;
+                }
+            }
+
+            if directory == root {
+                break;
+            }
+        }
+
+        // Apply files from the furthest, so that closer ones override them.
+        let mut config = EditorConfig::default();
+        for (directory, content) in files.iter().rev() {
+            let relative_path = match path.strip_prefix(directory) {
+                Ok(relative_path) => relative_path.to_string_lossy().replace('\\', "/"),
+                Err(_) => continue,
+            };
+
+            for (pattern, properties) in parse(content).1 {
+                if section_matches(&pattern, &relative_path) {
+                    for (key, value) in properties {
+                        config.set(&key, &value);
+                    }
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Applies the properties to the buffer's indentation and save options.
+    /// Of the supported charsets, only `utf-8` and `utf-8-bom` are applied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::{Buffer, EditorConfig};
+    /// use scribe::buffer::{IndentStyle, LineEnding};
+    /// use scribe::editorconfig::IndentSize;
+    ///
+    /// let mut buffer = Buffer::new();
+    /// let config = EditorConfig {
+    ///     indent_style: Some(IndentStyle::Spaces),
+    ///     indent_size: Some(IndentSize::Columns(2)),
+    ///     end_of_line: Some(LineEnding::CrLf),
+    ///     ..Default::default()
+    /// };
+    /// config.apply(&mut buffer);
+    ///
+    /// assert_eq!(buffer.indentation.width, 2);
+    /// assert_eq!(buffer.save_options.line_ending, Some(LineEnding::CrLf));
+    /// ```
+    pub fn apply(&self, buffer: &mut Buffer) {
+        if let Some(style) = self.indent_style {
+            buffer.indentation.style = style;
+        }
+
+        let indent_size = match self.indent_size {
+            Some(IndentSize::Columns(size)) => Some(size),
+            Some(IndentSize::Tab) => self.tab_width,
+            None => None,
+        };
+        let width = match buffer.indentation.style {
+            IndentStyle::Tabs => self.tab_width.or(indent_size),
+            IndentStyle::Spaces => indent_size.or(self.tab_width),
+        };
+        if let Some(width) = width {
+            buffer.indentation.width = width;
+        }
+
+        if let Some(line_ending) = self.end_of_line {
+            buffer.save_options.line_ending = Some(line_ending);
+        }
+        match self.charset.as_deref() {
+            Some("utf-8") => buffer.save_options.byte_order_mark = false,
+            Some("utf-8-bom") => buffer.save_options.byte_order_mark = true,
+            _ => (),
+        }
+        if let Some(trim) = self.trim_trailing_whitespace {
+            buffer.save_options.trim_trailing_whitespace = trim;
+        }
+        if let Some(insert) = self.insert_final_newline {
+            buffer.save_options.insert_final_newline = insert;
+        }
+    }
+
+    // Sets a property using its raw value, ignoring unknown
+    // properties and values, and clearing those that are unset.
+    fn set(&mut self, key: &str, value: &str) {
+        let value = value.to_lowercase();
+        let unset = value == "unset";
+        let flag = match value.as_str() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        };
+
+        match key {
+            "indent_style" => {
+                self.indent_style = match value.as_str() {
+                    "tab" => Some(IndentStyle::Tabs),
+                    "space" => Some(IndentStyle::Spaces),
+                    _ if unset => None,
+                    _ => return,
+                }
+            }
+            "indent_size" => {
+                self.indent_size = match value.as_str() {
+                    "tab" => Some(IndentSize::Tab),
+                    _ if unset => None,
+                    _ => match value.parse() {
+                        Ok(size) => Some(IndentSize::Columns(size)),
+                        Err(_) => return,
+                    },
+                }
+            }
+            "tab_width" => {
+                self.tab_width = match value.parse() {
+                    Ok(width) => Some(width),
+                    Err(_) if unset => None,
+                    Err(_) => return,
+                }
+            }
+            "end_of_line" => {
+                self.end_of_line = match value.as_str() {
+                    "lf" => Some(LineEnding::Lf),
+                    "crlf" => Some(LineEnding::CrLf),
+                    "cr" => Some(LineEnding::Cr),
+                    _ if unset => None,
+                    _ => return,
+                }
+            }
+            "charset" => self.charset = if unset { None } else { Some(value) },
+            "trim_trailing_whitespace" if flag.is_some() || unset => {
+                self.trim_trailing_whitespace = flag
+            }
+            "insert_final_newline" if flag.is_some() || unset => self.insert_final_newline = flag,
+            _ => (),
+        }
+    }
+}
+
+type Section = (String, Vec<(String, String)>);
+
+// Parses file content into its root flag and sections. Keys are lowercased;
+// properties preceding the first section are only used for the root flag.
+fn parse(content: &str) -> (bool, Vec<Section>) {
+    let mut root = false;
+    let mut sections: Vec<Section> = Vec::new();
+
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(pattern) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push((pattern.to_string(), Vec::new()));
+        } else if let Some((key, value)) = line.split_once('=') {
+            let (key, value) = (key.trim().to_lowercase(), value.trim().to_string());
+
+            match sections.last_mut() {
+                Some((_, properties)) => properties.push((key, value)),
+                None if key == "root" => root = value.eq_ignore_ascii_case("true"),
+                None => (),
+            }
+        }
+    }
+
+    (root, sections)
+}
+
+// Whether a section's glob pattern matches the path, which is relative to the
+// directory containing the file. Patterns without a slash match file names
+// in any directory; those with one are relative to the file's directory.
+pub(crate) fn section_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    if !pattern.contains(&'/') {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        return glob_matches(&pattern, &name.chars().collect::<Vec<_>>());
+    }
+
+    let pattern = pattern.strip_prefix(&['/']).unwrap_or(&pattern);
+    glob_matches(pattern, &path.chars().collect::<Vec<_>>())
+}
+
+// Matches a path against an EditorConfig glob, which supports `*`, `**`, `?`,
+// `[seq]`, `[!seq]`, `{s1,s2}`, `{n1..n2}`, and backslash escapes.
+fn glob_matches(pattern: &[char], path: &[char]) -> bool {
+    let (&first, rest) = match pattern.split_first() {
+        Some(split) => split,
+        None => return path.is_empty(),
+    };
+
+    match first {
+        '*' if rest.first() == Some(&'*') => {
+            (0..=path.len()).any(|index| glob_matches(&rest[1..], &path[index..]))
+        }
+        '*' => (0..=path.len())
+            .take_while(|&index| index == 0 || path[index - 1] != '/')
+            .any(|index| glob_matches(rest, &path[index..])),
+        '?' => match path.split_first() {
+            Some((&character, path)) => character != '/' && glob_matches(rest, path),
+            None => false,
+        },
+        '[' => match rest.iter().position(|&c| c == ']') {
+            Some(end) if end > 0 => {
+                let (class, rest) = (&rest[..end], &rest[end + 1..]);
+                match path.split_first() {
+                    Some((&character, path)) => {
+                        class_matches(class, character) && glob_matches(rest, path)
+                    }
+                    None => false,
+                }
+            }
+            _ => literal_matches(first, rest, path),
+        },
+        '{' => match closing_brace(rest) {
+            Some(end) => {
+                let (group, rest) = (&rest[..end], &rest[end + 1..]);
+                braces_match(group, rest, path)
+            }
+            None => literal_matches(first, rest, path),
+        },
+        '\\' => match rest.split_first() {
+            Some((&escaped, rest)) => literal_matches(escaped, rest, path),
+            None => literal_matches(first, rest, path),
+        },
+        _ => literal_matches(first, rest, path),
+    }
+}
+
+fn literal_matches(character: char, pattern: &[char], path: &[char]) -> bool {
+    path.first() == Some(&character) && glob_matches(pattern, &path[1..])
+}
+
+// Matches a character against the contents of a bracket expression.
+fn class_matches(class: &[char], character: char) -> bool {
+    let (negated, class) = match class.split_first() {
+        Some(('!', class)) => (true, class),
+        _ => (false, class),
+    };
+
+    let mut matched = false;
+    let mut index = 0;
+    while index < class.len() {
+        if index + 2 < class.len() && class[index + 1] == '-' {
+            matched |= class[index] <= character && character <= class[index + 2];
+            index += 3;
+        } else {
+            matched |= class[index] == character;
+            index += 1;
+        }
+    }
+
+    matched != negated
+}
+
+// Finds the brace closing the group that starts the pattern, accounting for nesting.
+fn closing_brace(pattern: &[char]) -> Option<usize> {
+    let mut depth = 0;
+    for (index, &character) in pattern.iter().enumerate() {
+        match character {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(index),
+            '}' => depth -= 1,
+            _ => (),
+        }
+    }
+
+    None
+}
+
+// Matches a brace group (either a numeric range or comma-separated
+// alternatives) followed by the rest of the pattern.
+fn braces_match(group: &[char], rest: &[char], path: &[char]) -> bool {
+    let text: String = group.iter().collect();
+    if let Some((start, end)) = text.split_once("..") {
+        if let (Ok(start), Ok(end)) = (start.parse::<i64>(), end.parse::<i64>()) {
+            let (start, end) = (start.min(end), start.max(end));
+
+            // Try every numeric prefix of the path.
+            return (1..=path.len()).any(|length| {
+                let number: String = path[..length].iter().collect();
+                number.parse::<i64>().is_ok_and(|n| n >= start && n <= end)
+                    && !number.starts_with('+')
+                    && glob_matches(rest, &path[length..])
+            });
+        }
+    }
+
+    // Split alternatives on top-level commas.
+    let mut alternatives = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (index, &character) in group.iter().enumerate() {
+        match character {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alternatives.push(&group[start..index]);
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+    alternatives.push(&group[start..]);
+
+    alternatives.into_iter().any(|alternative| {
+        let pattern: Vec<char> = alternative.iter().chain(rest).cloned().collect();
+        glob_matches(&pattern, path)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{section_matches, EditorConfig, IndentSize};
+    use crate::buffer::{IndentStyle, LineEnding};
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn section_matches_file_names_in_any_directory() {
+        assert!(section_matches("*.rs", "src/buffer/mod.rs"));
+        assert!(section_matches("*", "src/lib.rs"));
+        assert!(!section_matches("*.rs", "src/lib.rb"));
+    }
+
+    #[test]
+    fn section_matches_paths_relative_to_config() {
+        assert!(section_matches("src/*.rs", "src/lib.rs"));
+        assert!(section_matches("/src/*.rs", "src/lib.rs"));
+        assert!(!section_matches("src/*.rs", "src/buffer/mod.rs"));
+        assert!(section_matches("src/**.rs", "src/buffer/mod.rs"));
+        assert!(!section_matches("src/*.rs", "lib/src/lib.rs"));
+    }
+
+    #[test]
+    fn section_matches_classes_alternatives_and_ranges() {
+        assert!(section_matches("file.[ch]", "file.c"));
+        assert!(!section_matches("file.[!ch]", "file.c"));
+        assert!(section_matches("file.[a-c]", "file.b"));
+        assert!(section_matches("*.{rs,toml}", "Cargo.toml"));
+        assert!(section_matches("{Makefile,*.{mk,make}}", "rules.mk"));
+        assert!(section_matches("file{1..10}.txt", "file7.txt"));
+        assert!(!section_matches("file{1..10}.txt", "file11.txt"));
+        assert!(section_matches("?.rs", "a.rs"));
+        assert!(section_matches("\\*.rs", "*.rs"));
+        assert!(!section_matches("\\*.rs", "a.rs"));
+    }
+
+    #[test]
+    fn resolve_prefers_closer_files_and_later_sections() {
+        let config = EditorConfig::resolve(
+            Path::new("tests/sample/editorconfig/nested/file.rs"),
+            Path::new("tests/sample/editorconfig"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            EditorConfig {
+                indent_style: Some(IndentStyle::Tabs),
+                indent_size: Some(IndentSize::Tab),
+                tab_width: Some(8),
+                end_of_line: Some(LineEnding::Lf),
+                charset: None,
+                trim_trailing_whitespace: Some(true),
+                insert_final_newline: Some(true),
+            }
+        );
+    }
+
+    #[test]
+    fn resolve_uses_section_patterns() {
+        let config = EditorConfig::resolve(
+            Path::new("tests/sample/editorconfig/file.md"),
+            Path::new("tests/sample/editorconfig"),
+        )
+        .unwrap();
+
+        assert_eq!(config.indent_style, Some(IndentStyle::Spaces));
+        assert_eq!(config.indent_size, Some(IndentSize::Columns(2)));
+        assert_eq!(config.trim_trailing_whitespace, Some(false));
+        assert_eq!(config.charset.as_deref(), Some("utf-8"));
+    }
+
+    #[test]
+    fn resolve_skips_unreadable_files() {
+        let directory = env::temp_dir().join("scribe_editorconfig_unreadable");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join(".editorconfig"),
+            b"[*]\nindent_style = \xff\n",
+        )
+        .unwrap();
+        fs::write(directory.join("file.rs"), "").unwrap();
+
+        let config = EditorConfig::resolve(&directory.join("file.rs"), &directory).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(config, EditorConfig::default());
+    }
+
+    #[test]
+    fn apply_uses_tab_width_for_tab_indentation() {
+        let mut buffer = crate::Buffer::new();
+        EditorConfig {
+            indent_style: Some(IndentStyle::Tabs),
+            indent_size: Some(IndentSize::Columns(2)),
+            tab_width: Some(8),
+            charset: Some(String::from("utf-8-bom")),
+            ..Default::default()
+        }
+        .apply(&mut buffer);
+
+        assert_eq!(buffer.indentation.style, IndentStyle::Tabs);
+        assert_eq!(buffer.indentation.width, 8);
+        assert!(buffer.save_options.byte_order_mark);
+    }
+}
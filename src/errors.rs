@@ -1,9 +1,25 @@
 error_chain! {
     errors {
+        CommandFailed(status: ::std::process::ExitStatus, stderr: String) {
+            description("command failed")
+            display("command failed with {}: {}", status, stderr)
+        }
+        InvalidMacroStep(step: String) {
+            description("invalid macro step")
+            display("invalid macro step: '{}'", step)
+        }
         EmptyWorkspace {
             description("the workspace is empty")
             display("the workspace is empty")
         }
+        InvalidRange {
+            description("range is outside of the buffer")
+            display("range is outside of the buffer")
+        }
+        MissingComment {
+            description("no comment delimiters for the current syntax")
+            display("no comment delimiters for the current syntax")
+        }
         MissingPath {
             description("buffer doesn't have a path")
             display("buffer doesn't have a path")
@@ -16,12 +32,22 @@ error_chain! {
             description("no syntax definition for the current buffer")
             display("no syntax definition for the current buffer")
         }
+        MissingTheme(name: String) {
+            description("theme not found")
+            display("couldn't find a theme named '{}'", name)
+        }
+        UnknownSyntax(name: String) {
+            description("syntax not found")
+            display("couldn't find a syntax named '{}'", name)
+        }
     }
 
     foreign_links {
         Io(::std::io::Error) #[cfg(unix)];
         ParsingError(syntect::parsing::ParsingError);
         ScopeError(syntect::parsing::ScopeError);
+        SyntectError(syntect::Error);
         SyntaxLoadingError(syntect::LoadingError);
+        Utf8Error(::std::string::FromUtf8Error);
     }
 }
@@ -0,0 +1,245 @@
+use crate::buffer::{LineRange, Token, TokenSet};
+use crate::errors::*;
+use crate::highlighting::Styler;
+use std::fmt::Write;
+use syntect::highlighting::{Color, FontStyle, Style};
+
+const RESET: &str = "\x1b[0m";
+
+// Channel intensities used by the 6x6x6 color cube in the 256-color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The colors available to the terminal rendering ANSI output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnsiColors {
+    /// 24-bit colors, used as-is.
+    TrueColor,
+
+    /// The xterm 256-color palette, to which colors are approximated.
+    Ansi256,
+}
+
+impl Styler {
+    /// Renders the tokens for the specified lines as text with ANSI escape
+    /// sequences, using the styler's theme. Lines are separated by newlines,
+    /// with styles reset at the end of each, and no trailing newline.
+    ///
+    /// Backgrounds are only set for tokens whose theme background differs
+    /// from the theme's default, leaving the terminal's own background in
+    /// place elsewhere.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Styler;
+    /// use scribe::buffer::{LineRange, TokenSet};
+    /// use scribe::highlighting::AnsiColors;
+    /// use syntect::parsing::SyntaxSet;
+    ///
+    /// let syntax_set = SyntaxSet::load_defaults_newlines();
+    /// let syntax = syntax_set.find_syntax_by_extension("rs").unwrap();
+    /// let tokens = TokenSet::new("// scribe\nfn main() {}".to_string(), syntax, &syntax_set);
+    /// let styler = Styler::from_default_theme("base16-ocean.dark").unwrap();
+    ///
+    /// let ansi = styler.to_ansi(&tokens, &LineRange::new(1, 2), AnsiColors::TrueColor).unwrap();
+    /// assert!(ansi.starts_with("\x1b[0;38;2;180;142;173mfn"));
+    /// assert!(ansi.ends_with("\x1b[0m"));
+    /// ```
+    pub fn to_ansi(
+        &self,
+        tokens: &TokenSet,
+        lines: &LineRange,
+        colors: AnsiColors,
+    ) -> Result<String> {
+        let background = self.theme().settings.background.unwrap_or(Color::BLACK);
+        let mut ansi = String::new();
+        let mut current_style = None;
+
+        let mut iterator = tokens.iter_lines(lines)?;
+        for token in iterator.by_ref() {
+            match token {
+                Token::Lexeme(lexeme) => {
+                    let style = self.style(&lexeme.scope);
+                    if current_style != Some(style) {
+                        ansi.push_str(&escape_sequence(style, background, colors));
+                        current_style = Some(style);
+                    }
+                    ansi.push_str(lexeme.value);
+                }
+                Token::Newline => {
+                    if current_style.take().is_some() {
+                        ansi.push_str(RESET);
+                    }
+                    ansi.push('\n');
+                }
+            }
+        }
+        if let Some(error) = iterator.error.take() {
+            return Err(error);
+        }
+        if current_style.is_some() {
+            ansi.push_str(RESET);
+        }
+
+        Ok(ansi)
+    }
+}
+
+// An SGR sequence that resets any previous attributes and applies the style.
+fn escape_sequence(style: Style, background: Color, colors: AnsiColors) -> String {
+    let mut sequence = String::from("\x1b[0");
+    if style.font_style.contains(FontStyle::BOLD) {
+        sequence.push_str(";1");
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        sequence.push_str(";3");
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        sequence.push_str(";4");
+    }
+
+    let foreground = blend(style.foreground, style.background);
+    write_color(&mut sequence, 38, foreground, colors);
+    if style.background != background {
+        write_color(
+            &mut sequence,
+            48,
+            blend(style.background, background),
+            colors,
+        );
+    }
+    sequence.push('m');
+
+    sequence
+}
+
+fn write_color(sequence: &mut String, layer: u8, color: Color, colors: AnsiColors) {
+    let _ = match colors {
+        AnsiColors::TrueColor => {
+            write!(sequence, ";{};2;{};{};{}", layer, color.r, color.g, color.b)
+        }
+        AnsiColors::Ansi256 => write!(sequence, ";{};5;{}", layer, ansi_256(color)),
+    };
+}
+
+// Terminals can't render translucent colors, so we
+// composite them over the color they're drawn on.
+fn blend(color: Color, base: Color) -> Color {
+    let alpha = u32::from(color.a);
+    let mix = |top: u8, bottom: u8| {
+        ((u32::from(top) * alpha + u32::from(bottom) * (255 - alpha)) / 255) as u8
+    };
+
+    Color {
+        r: mix(color.r, base.r),
+        g: mix(color.g, base.g),
+        b: mix(color.b, base.b),
+        a: 0xFF,
+    }
+}
+
+// The nearest color in the 256-color palette, drawn from either its color
+// cube (16-231) or grayscale ramp (232-255). The first 16 colors are skipped,
+// since terminals commonly redefine them.
+fn ansi_256(color: Color) -> u8 {
+    let distance = |r: u8, g: u8, b: u8| {
+        [(color.r, r), (color.g, g), (color.b, b)]
+            .iter()
+            .map(|&(a, b)| (i32::from(a) - i32::from(b)).pow(2))
+            .sum::<i32>()
+    };
+    let nearest_level = |channel: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&index| (i32::from(CUBE_LEVELS[index]) - i32::from(channel)).abs())
+            .unwrap_or(0)
+    };
+
+    let (r, g, b) = (
+        nearest_level(color.r),
+        nearest_level(color.g),
+        nearest_level(color.b),
+    );
+    let cube_index = 16 + 36 * r + 6 * g + b;
+    let cube_distance = distance(CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+
+    // Grays run from 8 to 238, in steps of 10.
+    let average = (u32::from(color.r) + u32::from(color.g) + u32::from(color.b)) / 3;
+    let gray_step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + 10 * gray_step;
+    let gray_distance = distance(gray, gray, gray);
+
+    if gray_distance < cube_distance {
+        232 + gray_step
+    } else {
+        cube_index as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ansi_256, blend, AnsiColors};
+    use crate::buffer::{LineRange, TokenSet};
+    use crate::highlighting::Styler;
+    use syntect::highlighting::Color;
+    use syntect::parsing::SyntaxSet;
+
+    fn color(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b, a: 0xFF }
+    }
+
+    #[test]
+    fn to_ansi_resets_styles_at_the_end_of_each_line() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let syntax = syntax_set.find_syntax_by_extension("rs").unwrap();
+        let data = "struct Buffer;\n\nfn main() {}\nlet x = 1;";
+        let tokens = TokenSet::new(data.to_string(), syntax, &syntax_set);
+        let styler = Styler::from_default_theme("base16-ocean.dark").unwrap();
+
+        let ansi = styler
+            .to_ansi(&tokens, &LineRange::new(0, 3), AnsiColors::Ansi256)
+            .unwrap();
+        let lines: Vec<&str> = ansi.split('\n').collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "\x1b[0;38;5;139mstruct\x1b[0;38;5;251m Buffer;\x1b[0m"
+        );
+        assert_eq!(lines[1], "");
+        assert!(lines[2].starts_with("\x1b[0;38;5;139mfn"));
+        assert!(lines[2].ends_with("\x1b[0m"));
+    }
+
+    #[test]
+    fn to_ansi_renders_nothing_for_empty_ranges() {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let syntax = syntax_set.find_syntax_by_extension("rs").unwrap();
+        let tokens = TokenSet::new("struct Buffer;".to_string(), syntax, &syntax_set);
+        let styler = Styler::from_default_theme("base16-ocean.dark").unwrap();
+
+        let ansi = styler
+            .to_ansi(&tokens, &LineRange::new(1, 1), AnsiColors::TrueColor)
+            .unwrap();
+        assert_eq!(ansi, "");
+    }
+
+    #[test]
+    fn ansi_256_uses_the_nearest_cube_or_gray_color() {
+        assert_eq!(ansi_256(color(255, 0, 0)), 196);
+        assert_eq!(ansi_256(color(0, 0, 0)), 16);
+        assert_eq!(ansi_256(color(255, 255, 255)), 231);
+        assert_eq!(ansi_256(color(128, 128, 128)), 244);
+        assert_eq!(ansi_256(color(180, 142, 173)), 139);
+    }
+
+    #[test]
+    fn blend_composites_translucent_colors() {
+        let translucent = Color {
+            a: 0x80,
+            ..color(255, 255, 255)
+        };
+
+        assert_eq!(blend(translucent, color(0, 0, 0)), color(128, 128, 128));
+        assert_eq!(blend(color(1, 2, 3), color(0, 0, 0)), color(1, 2, 3));
+    }
+}
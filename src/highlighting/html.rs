@@ -0,0 +1,354 @@
+use crate::buffer::{Buffer, LineRange, Position, Range, Token};
+use crate::errors::*;
+use crate::highlighting::Styler;
+use std::fmt::Write;
+use syntect::highlighting::{Color, FontStyle, Style};
+use syntect::html::{css_for_theme_with_class_style, ClassStyle};
+use syntect::parsing::{Scope, SyntaxSet};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Options controlling how a buffer is rendered as HTML.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HtmlOptions {
+    /// Mark up tokens with CSS classes derived from their scopes (e.g.
+    /// `comment line rust`), along with a stylesheet generated from the
+    /// theme, rather than using inline styles.
+    pub css_classes: bool,
+
+    /// Prefix each line with its (one-based) line number.
+    pub line_numbers: bool,
+
+    /// Lines (zero-based) to highlight using the theme's line highlight color.
+    pub highlighted_lines: Vec<usize>,
+}
+
+impl Buffer {
+    /// Renders the range (or the entire buffer, if no range is provided) as
+    /// a standalone HTML document, styling its tokens using the styler's
+    /// theme. Returns an `ErrorKind::InvalidRange` error if the range is
+    /// outside of the buffer.
+    ///
+    /// The syntax set should be the one from which the buffer's syntax
+    /// definition was sourced; buffers without one are rendered as plain text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::{Buffer, Styler};
+    /// use scribe::highlighting::HtmlOptions;
+    /// use syntect::parsing::SyntaxSet;
+    ///
+    /// let syntax_set = SyntaxSet::load_defaults_newlines();
+    /// let styler = Styler::from_default_theme("InspiredGitHub").unwrap();
+    /// let mut buffer = Buffer::new();
+    /// buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+    /// buffer.insert("// scribe <3");
+    ///
+    /// let html = buffer.to_html(&syntax_set, &styler, None, &HtmlOptions::default()).unwrap();
+    /// assert!(html.contains("> scribe &lt;3</span>"));
+    /// ```
+    pub fn to_html(
+        &self,
+        syntax_set: &SyntaxSet,
+        styler: &Styler,
+        range: Option<Range>,
+        options: &HtmlOptions,
+    ) -> Result<String> {
+        let range = range.unwrap_or_else(|| Range::new(Position::new(), self.end_position()));
+        if self.read(&range).is_none() {
+            bail!(ErrorKind::InvalidRange);
+        }
+
+        // Exclude the last line if the range ends before its first character.
+        let first_line = range.start().line;
+        let mut last_line = range.end().line;
+        if range.end().offset == 0 && last_line > first_line {
+            last_line -= 1;
+        }
+
+        // Collect each line's lexemes, clipped to the range.
+        let tokens = self.tokens(syntax_set);
+        let mut iterator = tokens.iter_lines(&LineRange::new(first_line, last_line + 1))?;
+        let mut lines = vec![Vec::new(); last_line - first_line + 1];
+        for token in iterator.by_ref() {
+            let lexeme = match token {
+                Token::Lexeme(lexeme) => lexeme,
+                Token::Newline => continue,
+            };
+            let position = lexeme.position;
+            let start = if position.line == range.start().line {
+                range.start().offset.saturating_sub(position.offset)
+            } else {
+                0
+            };
+            let end = if position.line == range.end().line {
+                range.end().offset.saturating_sub(position.offset)
+            } else {
+                usize::MAX
+            };
+
+            let value = clip(lexeme.value, start, end);
+            if !value.is_empty() {
+                lines[position.line - first_line].push((value, lexeme.scope));
+            }
+        }
+        if let Some(error) = iterator.error.take() {
+            return Err(error);
+        }
+
+        let theme = styler.theme();
+        let foreground = theme.settings.foreground.unwrap_or(Color::BLACK);
+        let background = theme.settings.background.unwrap_or(Color::WHITE);
+        let line_highlight = theme.settings.line_highlight.unwrap_or(Color {
+            a: 0x33,
+            ..foreground
+        });
+        let number_width = (last_line + 1).to_string().len();
+
+        let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        if options.css_classes {
+            html.push_str("<style>\n");
+            html.push_str(&css_for_theme_with_class_style(theme, ClassStyle::Spaced)?);
+            html.push_str(".line { min-height: 1.2em; }\n");
+            html.push_str(".line-number { opacity: 0.5; user-select: none; }\n");
+            let _ = writeln!(
+                html,
+                ".highlighted {{ background-color: {}; }}",
+                css_color(line_highlight)
+            );
+            html.push_str("</style>\n</head>\n<body>\n<pre class=\"code\">");
+        } else {
+            let _ = write!(
+                html,
+                "</head>\n<body>\n<pre style=\"color: {}; background-color: {};\">",
+                css_color(foreground),
+                css_color(background)
+            );
+        }
+
+        for (index, lexemes) in lines.iter().enumerate() {
+            let line = first_line + index;
+            let highlighted = options.highlighted_lines.contains(&line);
+
+            if options.css_classes {
+                let class = if highlighted {
+                    "line highlighted"
+                } else {
+                    "line"
+                };
+                let _ = write!(html, "<div class=\"{}\">", class);
+            } else if highlighted {
+                let _ = write!(
+                    html,
+                    "<div style=\"min-height: 1.2em; background-color: {};\">",
+                    css_color(line_highlight)
+                );
+            } else {
+                html.push_str("<div style=\"min-height: 1.2em;\">");
+            }
+
+            if options.line_numbers {
+                let number = format!("{:>width$} ", line + 1, width = number_width);
+                if options.css_classes {
+                    let _ = write!(html, "<span class=\"line-number\">{}</span>", number);
+                } else {
+                    let _ = write!(html, "<span style=\"opacity: 0.5;\">{}</span>", number);
+                }
+            }
+
+            if options.css_classes {
+                // Nest spans for each scope, so that the stylesheet's
+                // descendant selectors (e.g. `.source .comment`) apply.
+                let mut open_scopes: &[Scope] = &[];
+                for (value, scope) in lexemes {
+                    let scopes = scope.as_slice();
+                    let shared = open_scopes
+                        .iter()
+                        .zip(scopes)
+                        .take_while(|(a, b)| a == b)
+                        .count();
+
+                    html.push_str(&"</span>".repeat(open_scopes.len() - shared));
+                    for scope in &scopes[shared..] {
+                        let _ = write!(
+                            html,
+                            "<span class=\"{}\">",
+                            scope.build_string().replace('.', " ")
+                        );
+                    }
+                    html.push_str(&escape(value));
+                    open_scopes = scopes;
+                }
+                html.push_str(&"</span>".repeat(open_scopes.len()));
+            } else {
+                for (value, scope) in lexemes {
+                    let _ = write!(
+                        html,
+                        "<span style=\"{}\">{}</span>",
+                        inline_style(styler.style(scope), background),
+                        escape(value)
+                    );
+                }
+            }
+
+            html.push_str("</div>");
+        }
+        html.push_str("</pre>\n</body>\n</html>\n");
+
+        Ok(html)
+    }
+}
+
+// The portion of the value between the grapheme offsets.
+fn clip(value: &str, start: usize, end: usize) -> &str {
+    let mut offsets = value
+        .grapheme_indices(true)
+        .map(|(offset, _)| offset)
+        .chain(Some(value.len()));
+    let start_offset = offsets.by_ref().nth(start).unwrap_or(value.len());
+    let end_offset = if end <= start {
+        start_offset
+    } else {
+        offsets.nth(end - start - 1).unwrap_or(value.len())
+    };
+
+    &value[start_offset..end_offset]
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn css_color(color: Color) -> String {
+    if color.a == 0xFF {
+        format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+    } else {
+        format!(
+            "#{:02x}{:02x}{:02x}{:02x}",
+            color.r, color.g, color.b, color.a
+        )
+    }
+}
+
+// Converts the style to CSS declarations, omitting the
+// background if it matches that of the document.
+fn inline_style(style: Style, background: Color) -> String {
+    let mut css = format!("color: {};", css_color(style.foreground));
+    if style.background != background {
+        let _ = write!(css, " background-color: {};", css_color(style.background));
+    }
+    if style.font_style.contains(FontStyle::BOLD) {
+        css.push_str(" font-weight: bold;");
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        css.push_str(" font-style: italic;");
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        css.push_str(" text-decoration: underline;");
+    }
+
+    css
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clip, HtmlOptions};
+    use crate::buffer::{Buffer, Position, Range};
+    use crate::errors::*;
+    use crate::highlighting::Styler;
+    use syntect::parsing::SyntaxSet;
+
+    fn render(content: &str, range: Option<Range>, options: &HtmlOptions) -> Result<String> {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let styler = Styler::from_default_theme("base16-ocean.dark").unwrap();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert(content);
+
+        buffer.to_html(&syntax_set, &styler, range, options)
+    }
+
+    // The document's body, between its pre tags.
+    fn body(html: &str) -> &str {
+        let start = html.find("<pre").unwrap();
+        let start = start + html[start..].find('>').unwrap() + 1;
+
+        &html[start..html.find("</pre>").unwrap()]
+    }
+
+    #[test]
+    fn clip_uses_grapheme_offsets() {
+        assert_eq!(clip("scribé", 4, 6), "bé");
+        assert_eq!(clip("scribé", 0, usize::MAX), "scribé");
+        assert_eq!(clip("scribé", 6, 8), "");
+        assert_eq!(clip("scribé", 2, 2), "");
+    }
+
+    #[test]
+    fn to_html_uses_inline_styles_by_default() {
+        let html = render("fn main() {}", None, &HtmlOptions::default()).unwrap();
+
+        assert!(!html.contains("<style>"));
+        assert!(html.contains("<pre style=\"color: #c0c5ce; background-color: #2b303b;\">"));
+        assert!(body(&html).starts_with(
+            "<div style=\"min-height: 1.2em;\"><span style=\"color: #b48ead;\">fn</span>"
+        ));
+    }
+
+    #[test]
+    fn to_html_nests_scope_classes_and_renders_line_options() {
+        let options = HtmlOptions {
+            css_classes: true,
+            line_numbers: true,
+            highlighted_lines: vec![1],
+        };
+        // The empty line following the trailing newline isn't rendered.
+        let html = render("// <scribe>\n\n", None, &options).unwrap();
+
+        assert!(html.contains(".highlighted { background-color: #65737e30; }"));
+        assert_eq!(
+            body(&html),
+            "<div class=\"line\"><span class=\"line-number\">1 </span>\
+             <span class=\"source rust\"><span class=\"comment line double-slash rust\">\
+             <span class=\"punctuation definition comment rust\">//</span> &lt;scribe&gt;\
+             </span></span></div>\
+             <div class=\"line highlighted\"><span class=\"line-number\">2 </span></div>"
+        );
+    }
+
+    #[test]
+    fn to_html_clips_lexemes_to_the_range() {
+        let options = HtmlOptions {
+            css_classes: true,
+            line_numbers: true,
+            ..Default::default()
+        };
+        let range = Range::new(
+            Position { line: 1, offset: 3 },
+            Position { line: 3, offset: 0 },
+        );
+        let html = render("scribe\nlibrary\neditor\nbuffer", Some(range), &options).unwrap();
+
+        assert_eq!(
+            body(&html),
+            "<div class=\"line\"><span class=\"line-number\">2 </span>\
+             <span class=\"source rust\">rary</span></div>\
+             <div class=\"line\"><span class=\"line-number\">3 </span>\
+             <span class=\"source rust\">editor</span></div>"
+        );
+    }
+
+    #[test]
+    fn to_html_rejects_invalid_ranges() {
+        let range = Range::new(Position::new(), Position { line: 2, offset: 0 });
+
+        match render("scribe", Some(range), &HtmlOptions::default()) {
+            Err(Error(ErrorKind::InvalidRange, _)) => (),
+            _ => panic!("expected an invalid range error"),
+        }
+    }
+}
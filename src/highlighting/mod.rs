@@ -0,0 +1,12 @@
+//! Theme-based styling of buffer tokens.
+
+// Published API
+pub use self::ansi::AnsiColors;
+pub use self::html::HtmlOptions;
+pub use self::styler::{StyledSpan, Styler};
+pub use syntect::highlighting::{Color, FontStyle, Style, Theme, ThemeSet};
+
+// Child modules
+mod ansi;
+mod html;
+mod styler;
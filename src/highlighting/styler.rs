@@ -0,0 +1,170 @@
+use crate::buffer::{Lexeme, Position, Token};
+use crate::errors::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+use syntect::highlighting::{Highlighter, Style, Theme, ThemeSet};
+use syntect::parsing::{Scope, ScopeStack};
+
+/// A lexeme, along with the theme style that applies to it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StyledSpan<'a> {
+    pub value: &'a str,
+    pub position: Position,
+    pub style: Style,
+}
+
+/// Applies a theme to tokens, producing styled spans.
+///
+/// Matching a scope stack against a theme's rules is relatively expensive,
+/// and tokens tend to share a small number of scope stacks, so the style
+/// for each stack is cached after it's first matched.
+pub struct Styler {
+    theme: Theme,
+    styles: RefCell<HashMap<Vec<Scope>, Style>>,
+}
+
+impl Styler {
+    /// Creates a styler that applies the specified theme.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Styler;
+    /// use syntect::highlighting::ThemeSet;
+    ///
+    /// let mut themes = ThemeSet::load_defaults();
+    /// let theme = themes.themes.remove("InspiredGitHub").unwrap();
+    /// let styler = Styler::new(theme);
+    ///
+    /// assert_eq!(styler.theme().name.as_deref(), Some("GitHub"));
+    /// ```
+    pub fn new(theme: Theme) -> Styler {
+        Styler {
+            theme,
+            styles: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Creates a styler using one of syntect's bundled themes
+    /// (e.g. "base16-ocean.dark" or "InspiredGitHub").
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Styler;
+    ///
+    /// assert!(Styler::from_default_theme("base16-ocean.dark").is_ok());
+    /// assert!(Styler::from_default_theme("scribe").is_err());
+    /// ```
+    pub fn from_default_theme(name: &str) -> Result<Styler> {
+        let mut themes = ThemeSet::load_defaults();
+        let theme = themes
+            .themes
+            .remove(name)
+            .ok_or_else(|| ErrorKind::MissingTheme(name.to_string()))?;
+
+        Ok(Styler::new(theme))
+    }
+
+    /// Creates a styler using the `.tmTheme` file at the specified path.
+    pub fn from_file(path: &Path) -> Result<Styler> {
+        Ok(Styler::new(ThemeSet::get_theme(path)?))
+    }
+
+    /// The theme used to style tokens.
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// The style that applies to the scope stack.
+    pub fn style(&self, scope: &ScopeStack) -> Style {
+        let scopes = scope.as_slice();
+        if let Some(style) = self.styles.borrow().get(scopes) {
+            return *style;
+        }
+
+        // Preparing a highlighter is relatively expensive, so it's only
+        // done when the style for a scope stack isn't already cached.
+        let style = Highlighter::new(&self.theme).style_for_stack(scopes);
+        self.styles.borrow_mut().insert(scopes.to_vec(), style);
+
+        style
+    }
+
+    /// Styles the lexeme using its scope.
+    pub fn style_lexeme<'a>(&self, lexeme: &Lexeme<'a>) -> StyledSpan<'a> {
+        StyledSpan {
+            value: lexeme.value,
+            position: lexeme.position,
+            style: self.style(&lexeme.scope),
+        }
+    }
+
+    /// Styles each lexeme in the tokens, skipping newlines (the spans'
+    /// positions can be used to determine line changes, instead).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Styler;
+    /// use scribe::buffer::TokenSet;
+    /// use syntect::parsing::SyntaxSet;
+    ///
+    /// let syntax_set = SyntaxSet::load_defaults_newlines();
+    /// let syntax = syntax_set.find_syntax_by_extension("rs").unwrap();
+    /// let tokens = TokenSet::new("struct Buffer;".to_string(), syntax, &syntax_set);
+    /// let styler = Styler::from_default_theme("base16-ocean.dark").unwrap();
+    ///
+    /// let spans: Vec<_> = styler.spans(tokens.iter().unwrap()).collect();
+    /// assert_eq!(spans[0].value, "struct");
+    /// assert_ne!(spans[0].style, spans[1].style);
+    /// ```
+    pub fn spans<'a, 's, T>(&'s self, tokens: T) -> impl Iterator<Item = StyledSpan<'a>> + 's
+    where
+        T: IntoIterator<Item = Token<'a>>,
+        T::IntoIter: 's,
+        'a: 's,
+    {
+        tokens.into_iter().filter_map(move |token| match token {
+            Token::Lexeme(lexeme) => Some(self.style_lexeme(&lexeme)),
+            Token::Newline => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Styler;
+    use crate::errors::*;
+    use syntect::parsing::{Scope, ScopeStack};
+
+    #[test]
+    fn style_matches_the_most_specific_theme_rule() {
+        let styler = Styler::from_default_theme("base16-ocean.dark").unwrap();
+        let mut scope = ScopeStack::new();
+        scope.push(Scope::new("source.rust").unwrap());
+        let default_style = styler.style(&scope);
+        scope.push(Scope::new("comment.line.double-slash.rust").unwrap());
+        let comment_style = styler.style(&scope);
+
+        assert_eq!(
+            default_style.background,
+            styler.theme().settings.background.unwrap()
+        );
+        assert_ne!(comment_style.foreground, default_style.foreground);
+
+        // Cached styles are returned for repeated stacks.
+        assert_eq!(styler.styles.borrow().len(), 2);
+        assert_eq!(styler.style(&scope), comment_style);
+        assert_eq!(styler.styles.borrow().len(), 2);
+    }
+
+    #[test]
+    fn from_default_theme_rejects_unknown_themes() {
+        match Styler::from_default_theme("scribe") {
+            Err(Error(ErrorKind::MissingTheme(name), _)) => assert_eq!(name, "scribe"),
+            _ => panic!("expected a missing theme error"),
+        }
+    }
+}
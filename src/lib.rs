@@ -1,3 +1,15 @@
+// Lints raised by the current toolchain against the existing tests
+#![cfg_attr(
+    test,
+    allow(
+        clippy::len_zero,
+        clippy::needless_borrow,
+        clippy::single_match,
+        clippy::unnecessary_to_owned,
+        clippy::vec_init_then_push
+    )
+)]
+
 // Syntax highlighting
 extern crate syntect;
 
@@ -9,10 +21,18 @@ extern crate unicode_segmentation;
 extern crate error_chain;
 
 pub mod buffer;
+pub mod editorconfig;
 mod errors;
+pub mod highlighting;
+mod modeline;
+mod registers;
+mod syntax_cache;
 pub mod util;
 mod workspace;
 
 pub use crate::buffer::Buffer;
+pub use crate::editorconfig::EditorConfig;
 pub use crate::errors::*;
+pub use crate::highlighting::Styler;
+pub use crate::registers::{Register, RegisterKind, Registers};
 pub use crate::workspace::Workspace;
@@ -0,0 +1,146 @@
+//! Syntax hints embedded in file content, using vim and Emacs modelines.
+
+// Vim only looks for modelines in this many lines at the start and end of a file.
+const VIM_MODELINE_LINES: usize = 5;
+
+// Vim options that name a file's syntax, in order of preference.
+const VIM_SYNTAX_OPTIONS: [&str; 4] = ["filetype", "ft", "syntax", "syn"];
+
+// Returns the syntax named by a modeline in the content, if any. Emacs
+// modelines (e.g. `-*- mode: python -*-`) are recognized on the first line,
+// or the second if the first is a shebang, and vim modelines (e.g.
+// `vim: set ft=python :`) in the first and last five lines.
+pub(crate) fn syntax_name(content: &str) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+
+    let emacs_lines = if lines.first().is_some_and(|line| line.starts_with("#!")) {
+        2
+    } else {
+        1
+    };
+    if let Some(name) = lines.iter().take(emacs_lines).find_map(|l| emacs_mode(l)) {
+        return Some(name);
+    }
+
+    let trailing_start = lines
+        .len()
+        .saturating_sub(VIM_MODELINE_LINES)
+        .max(VIM_MODELINE_LINES);
+    lines
+        .iter()
+        .take(VIM_MODELINE_LINES)
+        .chain(lines.iter().skip(trailing_start))
+        .find_map(|line| vim_filetype(line))
+}
+
+// Parses `-*- mode: name -*-` or the shorthand `-*- name -*-`.
+fn emacs_mode(line: &str) -> Option<String> {
+    let start = line.find("-*-")? + 3;
+    let end = start + line[start..].find("-*-")?;
+    let variables = line[start..end].trim();
+
+    if !variables.contains(':') {
+        return Some(variables.to_string()).filter(|mode| !mode.is_empty());
+    }
+
+    variables.split(';').find_map(|variable| {
+        let (key, value) = variable.split_once(':')?;
+        if key.trim().eq_ignore_ascii_case("mode") {
+            Some(value.trim().to_string()).filter(|mode| !mode.is_empty())
+        } else {
+            None
+        }
+    })
+}
+
+// Parses either form of vim modeline: `vim: ft=name` (options separated by
+// whitespace or colons), or `vim: set ft=name :` (options ending at a colon).
+// `vi:` and `ex:` markers are also recognized, when preceded by whitespace.
+fn vim_filetype(line: &str) -> Option<String> {
+    let options = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        line.match_indices(marker).find_map(|(index, _)| {
+            let preceding = line[..index].chars().next_back();
+            let valid = match preceding {
+                None => *marker == "vim:",
+                Some(character) => character.is_whitespace(),
+            };
+
+            if valid {
+                Some(&line[index + marker.len()..])
+            } else {
+                None
+            }
+        })
+    })?;
+
+    let options = options.trim_start();
+    let options: Vec<&str> = match options
+        .strip_prefix("set ")
+        .or_else(|| options.strip_prefix("se "))
+    {
+        Some(set) => set.split(':').next()?.split_whitespace().collect(),
+        None => options
+            .split(|c: char| c == ':' || c.is_whitespace())
+            .collect(),
+    };
+
+    VIM_SYNTAX_OPTIONS.iter().find_map(|name| {
+        options
+            .iter()
+            .find_map(|option| match option.split_once('=') {
+                Some((key, value)) if key == *name && !value.is_empty() => Some(value.to_string()),
+                _ => None,
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::syntax_name;
+
+    #[test]
+    fn syntax_name_parses_emacs_modelines() {
+        assert_eq!(
+            syntax_name("# -*- mode: python; coding: utf-8 -*-\n"),
+            Some("python".to_string())
+        );
+        assert_eq!(syntax_name("/* -*- C++ -*- */\n"), Some("C++".to_string()));
+        assert_eq!(
+            syntax_name("#!/bin/sh\n# -*- Mode: ruby -*-\n"),
+            Some("ruby".to_string())
+        );
+
+        // Only the first line is considered, unless it's a shebang.
+        assert_eq!(syntax_name("\n# -*- mode: python -*-\n"), None);
+    }
+
+    #[test]
+    fn syntax_name_parses_vim_modelines() {
+        assert_eq!(
+            syntax_name("# vim: set ts=4 ft=python :\n"),
+            Some("python".to_string())
+        );
+        assert_eq!(
+            syntax_name("// vim: ts=4:syntax=rust\n"),
+            Some("rust".to_string())
+        );
+        assert_eq!(
+            syntax_name("# vi: syn=sh ft=bash\n"),
+            Some("bash".to_string())
+        );
+
+        // Markers must follow whitespace (or start the line, for `vim:`).
+        assert_eq!(syntax_name("# nvim: ft=python\n"), None);
+        assert_eq!(syntax_name("vi: ft=python\n"), None);
+    }
+
+    #[test]
+    fn syntax_name_only_checks_vim_modelines_near_the_start_and_end() {
+        let mut lines = vec!["x"; 20];
+        lines[10] = "# vim: ft=python";
+        assert_eq!(syntax_name(&lines.join("\n")), None);
+
+        lines[16] = "# vim: ft=ruby";
+        assert_eq!(syntax_name(&lines.join("\n")), Some("ruby".to_string()));
+    }
+}
@@ -0,0 +1,622 @@
+//! Cut/copy/paste storage, shared by all buffers in a workspace.
+
+use crate::buffer::{Buffer, GapBuffer, Insert, LineRange, Position, Range};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Weak;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The number of entries retained in the register history, by default.
+pub const DEFAULT_HISTORY_SIZE: usize = 9;
+
+/// Whether register content represents complete lines or arbitrary text,
+/// which determines where it's placed when pasted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RegisterKind {
+    Characterwise,
+    Linewise,
+}
+
+/// Content that has been cut or copied from a buffer.
+/// Linewise content always ends with a newline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Register {
+    pub content: String,
+    pub kind: RegisterKind,
+}
+
+/// A store of cut/copied content, consisting of named registers, an unnamed
+/// register holding the most recent cut or copy, and a numbered history of
+/// recent cuts. The history doubles as a kill ring: pasting from the unnamed
+/// register and then calling `Buffer::yank_pop` replaces the pasted content
+/// with progressively older history entries.
+pub struct Registers {
+    named: HashMap<char, Register>,
+    unnamed: Option<Register>,
+    history: VecDeque<Register>,
+    history_size: usize,
+    last_paste: Option<Paste>,
+}
+
+// Tracks the most recent paste from the unnamed register, so that it can be
+// cycled, along with the buffer it was pasted into. The latter is identified
+// by its data, since buffers outside of a workspace don't have IDs.
+struct Paste {
+    buffer: Weak<RefCell<GapBuffer>>,
+    range: Range,
+    content: String,
+    index: Option<usize>,
+}
+
+impl Default for Registers {
+    fn default() -> Self {
+        Registers {
+            named: HashMap::new(),
+            unnamed: None,
+            history: VecDeque::new(),
+            history_size: DEFAULT_HISTORY_SIZE,
+            last_paste: None,
+        }
+    }
+}
+
+impl Registers {
+    /// Creates a new, empty register store.
+    pub fn new() -> Registers {
+        Default::default()
+    }
+
+    /// Stores cut content at the front of the history and in the unnamed
+    /// register, along with the named register, if one is specified. The
+    /// oldest history entry is discarded if the history is full.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::{Register, RegisterKind, Registers};
+    ///
+    /// let mut registers = Registers::new();
+    /// let register = Register {
+    ///     content: "scribe".to_string(),
+    ///     kind: RegisterKind::Characterwise,
+    /// };
+    /// registers.store(Some('a'), register.clone());
+    ///
+    /// assert_eq!(registers.named('a'), Some(&register));
+    /// assert_eq!(registers.numbered(1), Some(&register));
+    /// ```
+    pub fn store(&mut self, name: Option<char>, register: Register) {
+        self.store_copy(name, register.clone());
+        self.history.push_front(register);
+        self.history.truncate(self.history_size);
+    }
+
+    /// Stores copied content in the unnamed register, along with the
+    /// named register, if one is specified. Unlike `store`, the content
+    /// isn't added to the history.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::{Register, RegisterKind, Registers};
+    ///
+    /// let mut registers = Registers::new();
+    /// let register = Register {
+    ///     content: "scribe".to_string(),
+    ///     kind: RegisterKind::Characterwise,
+    /// };
+    /// registers.store_copy(None, register.clone());
+    ///
+    /// assert_eq!(registers.unnamed(), Some(&register));
+    /// assert!(registers.numbered(1).is_none());
+    /// ```
+    pub fn store_copy(&mut self, name: Option<char>, register: Register) {
+        if let Some(name) = name {
+            self.named.insert(name, register.clone());
+        }
+
+        self.unnamed = Some(register);
+        self.last_paste = None;
+    }
+
+    /// Returns the content of the specified named register.
+    pub fn named(&self, name: char) -> Option<&Register> {
+        self.named.get(&name)
+    }
+
+    /// Returns the most recently cut or copied content.
+    pub fn unnamed(&self) -> Option<&Register> {
+        self.unnamed.as_ref()
+    }
+
+    /// Returns the specified history entry, where `1` is the most recent.
+    pub fn numbered(&self, number: usize) -> Option<&Register> {
+        number
+            .checked_sub(1)
+            .and_then(|index| self.history.get(index))
+    }
+
+    /// The number of entries retained in the history.
+    pub fn history_size(&self) -> usize {
+        self.history_size
+    }
+
+    /// Sets the number of entries retained in the
+    /// history, discarding the oldest beyond it.
+    pub fn set_history_size(&mut self, size: usize) {
+        self.history_size = size;
+        self.history.truncate(size);
+        self.last_paste = None;
+    }
+
+    // Looks up a named register, falling back to the unnamed register.
+    fn get(&self, name: Option<char>) -> Option<&Register> {
+        match name {
+            Some(name) => self.named(name),
+            None => self.unnamed(),
+        }
+    }
+}
+
+impl Buffer {
+    /// Removes the range from the buffer, storing it in the registers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::{Buffer, Registers};
+    /// use scribe::buffer::{Position, Range};
+    ///
+    /// let mut buffer = Buffer::new();
+    /// let mut registers = Registers::new();
+    /// buffer.insert("scribe library");
+    /// buffer.cut_range(
+    ///     Range::new(
+    ///         Position{ line: 0, offset: 6 },
+    ///         Position{ line: 0, offset: 14 }
+    ///     ),
+    ///     &mut registers,
+    ///     None
+    /// );
+    ///
+    /// assert_eq!(buffer.data(), "scribe");
+    /// assert_eq!(registers.numbered(1).unwrap().content, " library");
+    /// ```
+    pub fn cut_range(&mut self, range: Range, registers: &mut Registers, name: Option<char>) {
+        if let Some(content) = self.read(&range) {
+            registers.store(
+                name,
+                Register {
+                    content,
+                    kind: RegisterKind::Characterwise,
+                },
+            );
+            self.delete_range(range);
+        }
+    }
+
+    /// Stores the range in the registers (see `Registers::store_copy`),
+    /// without modifying the buffer.
+    pub fn copy_range(&self, range: &Range, registers: &mut Registers, name: Option<char>) {
+        if let Some(content) = self.read(range) {
+            registers.store_copy(
+                name,
+                Register {
+                    content,
+                    kind: RegisterKind::Characterwise,
+                },
+            );
+        }
+    }
+
+    /// Removes the lines from the buffer, storing them in the registers.
+    /// Like other `LineRange` operations, the end line is excluded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::{Buffer, RegisterKind, Registers};
+    /// use scribe::buffer::LineRange;
+    ///
+    /// let mut buffer = Buffer::new();
+    /// let mut registers = Registers::new();
+    /// buffer.insert("scribe\nlibrary\neditor");
+    /// buffer.cut_lines(&LineRange::new(1, 3), &mut registers, None);
+    ///
+    /// assert_eq!(buffer.data(), "scribe");
+    /// assert_eq!(registers.numbered(1).unwrap().content, "library\neditor\n");
+    /// assert_eq!(registers.numbered(1).unwrap().kind, RegisterKind::Linewise);
+    /// ```
+    pub fn cut_lines(&mut self, lines: &LineRange, registers: &mut Registers, name: Option<char>) {
+        if let Some((range, content)) = self.line_extent(lines) {
+            registers.store(
+                name,
+                Register {
+                    content,
+                    kind: RegisterKind::Linewise,
+                },
+            );
+            self.delete_range(range);
+        }
+    }
+
+    /// Stores the lines in the registers (see `Registers::store_copy`),
+    /// without modifying the buffer. Like other `LineRange` operations,
+    /// the end line is excluded.
+    pub fn copy_lines(&self, lines: &LineRange, registers: &mut Registers, name: Option<char>) {
+        if let Some((_, content)) = self.line_extent(lines) {
+            registers.store_copy(
+                name,
+                Register {
+                    content,
+                    kind: RegisterKind::Linewise,
+                },
+            );
+        }
+    }
+
+    /// Inserts the content of the named register, or the unnamed register
+    /// if no name is specified, as a single undoable operation.
+    /// Characterwise content is inserted at the cursor, which is moved to the
+    /// end of it; linewise content is inserted below the cursor's line, and
+    /// the cursor is moved to the start of it. Returns false if the register
+    /// is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::{Buffer, Registers};
+    /// use scribe::buffer::{LineRange, Position};
+    ///
+    /// let mut buffer = Buffer::new();
+    /// let mut registers = Registers::new();
+    /// buffer.insert("scribe\nlibrary");
+    /// buffer.copy_lines(&LineRange::new(0, 1), &mut registers, None);
+    ///
+    /// buffer.cursor.move_to(Position{ line: 1, offset: 3 });
+    /// buffer.paste(&mut registers, None);
+    ///
+    /// assert_eq!(buffer.data(), "scribe\nlibrary\nscribe");
+    /// assert_eq!(*buffer.cursor, Position{ line: 2, offset: 0 });
+    /// ```
+    pub fn paste(&mut self, registers: &mut Registers, name: Option<char>) -> bool {
+        let register = match registers.get(name) {
+            Some(register) => register.clone(),
+            None => return false,
+        };
+
+        let position = match register.kind {
+            RegisterKind::Characterwise => self.cursor.position,
+            RegisterKind::Linewise => {
+                let next_line = Position {
+                    line: self.cursor.line + 1,
+                    offset: 0,
+                };
+
+                if next_line.line < self.line_count() {
+                    next_line
+                } else {
+                    self.end_position()
+                }
+            }
+        };
+
+        let paste = self.in_operation_group(|buffer| buffer.insert_register(&register, position));
+
+        // Only the unnamed register participates in the kill ring. If it
+        // holds the most recent cut, cycling continues from the entry after it.
+        registers.last_paste = match name {
+            Some(_) => None,
+            None => Some(Paste {
+                index: (registers.history.front() == Some(&register)).then_some(0),
+                ..paste
+            }),
+        };
+
+        true
+    }
+
+    /// Replaces content that was just pasted from the unnamed register with
+    /// the next oldest history entry, wrapping around to the most recent.
+    /// Returns false if the last paste can't be cycled, either because it
+    /// came from a named register, or it's since been modified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::{Buffer, Register, RegisterKind, Registers};
+    ///
+    /// let mut buffer = Buffer::new();
+    /// let mut registers = Registers::new();
+    /// for content in ["scribe", "library"] {
+    ///     registers.store(None, Register {
+    ///         content: content.to_string(),
+    ///         kind: RegisterKind::Characterwise,
+    ///     });
+    /// }
+    ///
+    /// buffer.paste(&mut registers, None);
+    /// assert_eq!(buffer.data(), "library");
+    ///
+    /// buffer.yank_pop(&mut registers);
+    /// assert_eq!(buffer.data(), "scribe");
+    /// ```
+    pub fn yank_pop(&mut self, registers: &mut Registers) -> bool {
+        let last_paste = match registers.last_paste.take() {
+            Some(paste) => paste,
+            None => return false,
+        };
+
+        // Make sure the pasted content is still in place.
+        if !last_paste.buffer.ptr_eq(&self.identity())
+            || self.read(&last_paste.range).as_ref() != Some(&last_paste.content)
+        {
+            return false;
+        }
+
+        let index = last_paste
+            .index
+            .map_or(0, |index| (index + 1) % registers.history.len().max(1));
+        let register = match registers.history.get(index) {
+            Some(register) => register.clone(),
+            None => return false,
+        };
+
+        let paste = self.in_operation_group(|buffer| {
+            buffer.delete_range(last_paste.range.clone());
+            buffer.insert_register(&register, last_paste.range.start())
+        });
+
+        registers.last_paste = Some(Paste {
+            index: Some(index),
+            ..paste
+        });
+
+        true
+    }
+
+    // Inserts register content at the specified position, moving the cursor
+    // accordingly. Linewise content inserted mid-line (i.e. at the end of the
+    // buffer) is placed on a new line below it.
+    fn insert_register(&mut self, register: &Register, position: Position) -> Paste {
+        let (content, start) = match register.kind {
+            RegisterKind::Linewise if position.offset > 0 => (
+                format!("\n{}", register.content.trim_end_matches('\n')),
+                Position {
+                    line: position.line + 1,
+                    offset: 0,
+                },
+            ),
+            _ => (register.content.clone(), position),
+        };
+
+        let end = end_of(&content, position);
+        self.apply_operation(Box::new(Insert::new(content.clone(), position)));
+
+        match register.kind {
+            RegisterKind::Characterwise => self.cursor.move_to(end),
+            RegisterKind::Linewise => self.cursor.move_to(start),
+        };
+
+        Paste {
+            buffer: self.identity(),
+            range: Range::new(position, end),
+            content,
+            index: None,
+        }
+    }
+}
+
+// The position at the end of content inserted at the specified position.
+fn end_of(content: &str, position: Position) -> Position {
+    let lines = content.matches('\n').count();
+    let last_line = content.rsplit('\n').next().unwrap_or("");
+    let offset = last_line.graphemes(true).count();
+
+    if lines == 0 {
+        Position {
+            line: position.line,
+            offset: position.offset + offset,
+        }
+    } else {
+        Position {
+            line: position.line + lines,
+            offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Register, RegisterKind, Registers};
+    use crate::buffer::{Buffer, LineRange, Position, Range};
+
+    fn characterwise(content: &str) -> Register {
+        Register {
+            content: content.to_string(),
+            kind: RegisterKind::Characterwise,
+        }
+    }
+
+    #[test]
+    fn store_discards_the_oldest_history_entries() {
+        let mut registers = Registers::new();
+        registers.set_history_size(2);
+        for content in ["first", "second", "third"] {
+            registers.store(None, characterwise(content));
+        }
+
+        assert_eq!(registers.numbered(1), Some(&characterwise("third")));
+        assert_eq!(registers.numbered(2), Some(&characterwise("second")));
+        assert!(registers.numbered(3).is_none());
+        assert!(registers.numbered(0).is_none());
+    }
+
+    #[test]
+    fn cut_lines_removes_preceding_newline_for_trailing_lines() {
+        let mut buffer = Buffer::new();
+        let mut registers = Registers::new();
+        buffer.insert("scribe\nlibrary");
+        buffer.cut_lines(&LineRange::new(1, 2), &mut registers, Some('a'));
+
+        assert_eq!(buffer.data(), "scribe");
+        assert_eq!(registers.named('a').unwrap().content, "library\n");
+    }
+
+    #[test]
+    fn paste_inserts_characterwise_content_at_cursor() {
+        let mut buffer = Buffer::new();
+        let mut registers = Registers::new();
+        buffer.insert("scribe");
+        registers.store(None, characterwise(" nी"));
+
+        buffer.cursor.move_to(Position { line: 0, offset: 6 });
+        assert!(buffer.paste(&mut registers, None));
+
+        assert_eq!(buffer.data(), "scribe nी");
+        assert_eq!(*buffer.cursor, Position { line: 0, offset: 8 });
+    }
+
+    #[test]
+    fn paste_inserts_linewise_content_below_the_cursor_line() {
+        let mut buffer = Buffer::new();
+        let mut registers = Registers::new();
+        buffer.insert("scribe\nlibrary\n");
+        buffer.copy_lines(&LineRange::new(1, 2), &mut registers, None);
+
+        buffer.cursor.move_to(Position { line: 0, offset: 2 });
+        buffer.paste(&mut registers, None);
+
+        assert_eq!(buffer.data(), "scribe\nlibrary\nlibrary\n");
+        assert_eq!(*buffer.cursor, Position { line: 1, offset: 0 });
+    }
+
+    #[test]
+    fn paste_returns_false_for_empty_registers() {
+        let mut buffer = Buffer::new();
+        let mut registers = Registers::new();
+
+        assert!(!buffer.paste(&mut registers, None));
+        assert!(!buffer.paste(&mut registers, Some('a')));
+    }
+
+    #[test]
+    fn paste_is_undone_as_a_single_operation() {
+        let mut buffer = Buffer::new();
+        let mut registers = Registers::new();
+        buffer.insert("scribe");
+        registers.store(None, characterwise(" library"));
+        buffer.cursor.move_to(Position { line: 0, offset: 6 });
+        buffer.paste(&mut registers, None);
+
+        buffer.undo();
+        assert_eq!(buffer.data(), "scribe");
+    }
+
+    #[test]
+    fn yank_pop_cycles_through_history_and_wraps() {
+        let mut buffer = Buffer::new();
+        let mut registers = Registers::new();
+        for content in ["first", "second", "third"] {
+            registers.store(None, characterwise(content));
+        }
+
+        buffer.paste(&mut registers, None);
+        assert_eq!(buffer.data(), "third");
+
+        assert!(buffer.yank_pop(&mut registers));
+        assert_eq!(buffer.data(), "second");
+
+        assert!(buffer.yank_pop(&mut registers));
+        assert_eq!(buffer.data(), "first");
+
+        assert!(buffer.yank_pop(&mut registers));
+        assert_eq!(buffer.data(), "third");
+        assert_eq!(*buffer.cursor, Position { line: 0, offset: 5 });
+    }
+
+    #[test]
+    fn yank_pop_does_nothing_after_the_paste_is_modified() {
+        let mut buffer = Buffer::new();
+        let mut registers = Registers::new();
+        registers.store(None, characterwise("first"));
+        registers.store(None, characterwise("second"));
+        buffer.paste(&mut registers, None);
+
+        buffer.cursor.move_to(Position::new());
+        buffer.delete();
+
+        assert!(!buffer.yank_pop(&mut registers));
+        assert_eq!(buffer.data(), "econd");
+    }
+
+    #[test]
+    fn yank_pop_does_nothing_in_other_buffers() {
+        let mut buffer = Buffer::new();
+        let mut other_buffer = Buffer::new();
+        let mut registers = Registers::new();
+        registers.store(None, characterwise("first"));
+        registers.store(None, characterwise("second"));
+        buffer.paste(&mut registers, None);
+        other_buffer.insert("second");
+
+        assert!(!other_buffer.yank_pop(&mut registers));
+        assert_eq!(other_buffer.data(), "second");
+    }
+
+    #[test]
+    fn yank_pop_replaces_copies_with_the_most_recent_cut() {
+        let mut buffer = Buffer::new();
+        let mut registers = Registers::new();
+        buffer.insert("scribe library");
+        buffer.cut_range(
+            Range::new(
+                Position { line: 0, offset: 6 },
+                Position {
+                    line: 0,
+                    offset: 14,
+                },
+            ),
+            &mut registers,
+            None,
+        );
+        buffer.copy_lines(&LineRange::new(0, 1), &mut registers, None);
+        assert!(registers.numbered(2).is_none());
+
+        buffer.cursor.move_to(Position { line: 0, offset: 6 });
+        buffer.paste(&mut registers, None);
+        assert_eq!(buffer.data(), "scribe\nscribe");
+
+        assert!(buffer.yank_pop(&mut registers));
+        assert_eq!(buffer.data(), "scribe library");
+    }
+
+    #[test]
+    fn yank_pop_does_nothing_after_pasting_a_named_register() {
+        let mut buffer = Buffer::new();
+        let mut registers = Registers::new();
+        registers.store(None, characterwise("first"));
+        registers.store(Some('a'), characterwise("second"));
+        buffer.paste(&mut registers, Some('a'));
+
+        assert!(!buffer.yank_pop(&mut registers));
+    }
+
+    #[test]
+    fn cut_range_stores_characterwise_content() {
+        let mut buffer = Buffer::new();
+        let mut registers = Registers::new();
+        buffer.insert("scribe\nlibrary");
+        buffer.cut_range(
+            Range::new(
+                Position { line: 0, offset: 3 },
+                Position { line: 1, offset: 3 },
+            ),
+            &mut registers,
+            None,
+        );
+
+        assert_eq!(buffer.data(), "scrrary");
+        assert_eq!(registers.numbered(1), Some(&characterwise("ibe\nlib")));
+    }
+}
@@ -0,0 +1,231 @@
+//! Caching of syntax sets built from user syntax definitions.
+
+use crate::errors::*;
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::UNIX_EPOCH;
+use syntect::dumps;
+use syntect::parsing::{Metadata, SyntaxSet};
+
+// Loads the default syntaxes, along with those in the definitions folder.
+//
+// Building a syntax set from a large folder is slow, so the result is dumped
+// to the cache directory (if one is provided), alongside a fingerprint of the
+// folder's contents. Subsequent loads use the dump if the fingerprint still
+// matches, and rebuild it otherwise. Syntect doesn't include metadata (e.g.
+// comment delimiters and indentation rules) when dumping a syntax set, so
+// it's dumped separately, and restored when the set is loaded. Failing to
+// read or write the cache isn't considered an error; the set is built from
+// the definitions instead.
+pub(crate) fn load(definitions: &Path, cache_directory: Option<&Path>) -> Result<SyntaxSet> {
+    let cache_directory = match cache_directory {
+        Some(directory) => directory,
+        None => return build(definitions),
+    };
+
+    let fingerprint = fingerprint(definitions)?;
+    let cache_path = cache_path(definitions, cache_directory);
+    if let Ok((cached_fingerprint, mut syntax_set, metadata)) =
+        dumps::from_uncompressed_dump_file::<(u64, SyntaxSet, Metadata), _>(&cache_path)
+    {
+        if cached_fingerprint == fingerprint {
+            syntax_set.set_metadata(metadata);
+            return Ok(syntax_set);
+        }
+    }
+
+    let syntax_set = build(definitions)?;
+    let _ = write(&cache_path, fingerprint, &syntax_set);
+
+    Ok(syntax_set)
+}
+
+// The platform's per-user cache directory for scribe, if one can be found.
+pub(crate) fn default_directory() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .map(|cache| cache.join("scribe"))
+}
+
+fn build(definitions: &Path) -> Result<SyntaxSet> {
+    let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+    builder.add_from_folder(definitions, true)?;
+
+    Ok(builder.build())
+}
+
+// Each definitions folder gets its own cache file, named using its path.
+fn cache_path(definitions: &Path, cache_directory: &Path) -> PathBuf {
+    let definitions = definitions
+        .canonicalize()
+        .unwrap_or_else(|_| definitions.to_path_buf());
+    let mut hasher = DefaultHasher::new();
+    definitions.hash(&mut hasher);
+
+    cache_directory.join(format!("syntaxes-{:016x}.packdump", hasher.finish()))
+}
+
+// Writes to a temporary file first, so that concurrent
+// loads never see a partially written cache.
+fn write(cache_path: &Path, fingerprint: u64, syntax_set: &SyntaxSet) -> Result<()> {
+    if let Some(directory) = cache_path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+
+    let temporary_path = cache_path.with_extension(format!("tmp{}", process::id()));
+    let dump = (fingerprint, syntax_set, syntax_set.metadata());
+    if let Err(error) = dumps::dump_to_uncompressed_file(&dump, &temporary_path) {
+        let _ = fs::remove_file(&temporary_path);
+        bail!(ErrorKind::Msg(error.to_string()));
+    }
+    fs::rename(&temporary_path, cache_path)?;
+
+    Ok(())
+}
+
+// A hash of the relative path, size, and modification time of every file in
+// the folder, along with the crate version (which determines the defaults).
+fn fingerprint(definitions: &Path) -> Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+
+    let mut directories = vec![definitions.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        let mut entries = fs::read_dir(&directory)?.collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                directories.push(entry.path());
+                continue;
+            }
+
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .unwrap_or_default();
+            entry
+                .path()
+                .strip_prefix(definitions)
+                .ok()
+                .hash(&mut hasher);
+            metadata.len().hash(&mut hasher);
+            modified.hash(&mut hasher);
+        }
+    }
+
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cache_path, fingerprint, load, write};
+    use crate::buffer::{Buffer, LineRange};
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use syntect::parsing::SyntaxSet;
+
+    // An empty scratch directory, unique to the test.
+    fn scratch_directory(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("scribe_syntax_cache_{}", name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+
+        path
+    }
+
+    fn has_scribe_syntax(syntax_set: &SyntaxSet) -> bool {
+        syntax_set.find_syntax_by_name("Scribe").is_some()
+    }
+
+    #[test]
+    fn load_uses_cached_syntax_sets_with_matching_fingerprints() {
+        let definitions = Path::new("tests/sample/syntaxes");
+        let cache_directory = scratch_directory("matching");
+
+        assert!(has_scribe_syntax(
+            &load(definitions, Some(&cache_directory)).unwrap()
+        ));
+        let cache = cache_path(definitions, &cache_directory);
+        assert!(cache.exists());
+
+        // Replace the cache with a set that's missing the
+        // folder's syntax, to confirm that it's being used.
+        let fingerprint = fingerprint(definitions).unwrap();
+        write(&cache, fingerprint, &SyntaxSet::load_defaults_newlines()).unwrap();
+        assert!(!has_scribe_syntax(
+            &load(definitions, Some(&cache_directory)).unwrap()
+        ));
+
+        // Stale caches are rebuilt.
+        write(
+            &cache,
+            fingerprint + 1,
+            &SyntaxSet::load_defaults_newlines(),
+        )
+        .unwrap();
+        assert!(has_scribe_syntax(
+            &load(definitions, Some(&cache_directory)).unwrap()
+        ));
+        assert!(has_scribe_syntax(
+            &load(definitions, Some(&cache_directory)).unwrap()
+        ));
+
+        fs::remove_dir_all(cache_directory).unwrap();
+    }
+
+    #[test]
+    fn load_restores_metadata_from_cached_syntax_sets() {
+        let definitions = Path::new("tests/sample/syntaxes");
+        let cache_directory = scratch_directory("metadata");
+        load(definitions, Some(&cache_directory)).unwrap();
+
+        let syntax_set = load(definitions, Some(&cache_directory)).unwrap();
+        let mut buffer = Buffer::new();
+        buffer.syntax_definition = syntax_set.find_syntax_by_extension("rs").cloned();
+        buffer.insert("scribe();");
+        buffer
+            .toggle_comment(&LineRange::new(0, 1), &syntax_set)
+            .unwrap();
+        assert_eq!(buffer.data(), "// scribe();");
+
+        fs::remove_dir_all(cache_directory).unwrap();
+    }
+
+    #[test]
+    fn load_builds_syntax_sets_without_a_cache_directory() {
+        let syntax_set = load(Path::new("tests/sample/syntaxes"), None).unwrap();
+        assert!(has_scribe_syntax(&syntax_set));
+    }
+
+    #[test]
+    fn fingerprint_changes_with_folder_contents() {
+        let definitions = scratch_directory("fingerprint");
+        let syntax_path = definitions.join("scribe.sublime-syntax");
+        fs::copy("tests/sample/syntaxes/scribe.sublime-syntax", &syntax_path).unwrap();
+
+        let original = fingerprint(&definitions).unwrap();
+        assert_eq!(fingerprint(&definitions).unwrap(), original);
+
+        let mut content = fs::read_to_string(&syntax_path).unwrap();
+        content.push_str("    - match: '\\bbuffer\\b'\n      scope: keyword.scribe\n");
+        fs::write(&syntax_path, content).unwrap();
+        let modified = fingerprint(&definitions).unwrap();
+        assert_ne!(modified, original);
+
+        fs::create_dir(definitions.join("nested")).unwrap();
+        fs::write(definitions.join("nested/empty.sublime-syntax"), "").unwrap();
+        assert_ne!(fingerprint(&definitions).unwrap(), modified);
+
+        fs::remove_dir_all(definitions).unwrap();
+    }
+}
@@ -1,10 +1,15 @@
 //! Buffer and working directory management.
 
-use crate::buffer::{Buffer, TokenSet};
+use crate::buffer::{Buffer, Range, ScopeStack, TokenSet};
+use crate::editorconfig::{self, EditorConfig};
 use crate::errors::*;
+use crate::highlighting::{HtmlOptions, Styler};
+use crate::modeline;
+use crate::registers::Registers;
+use crate::syntax_cache;
 use std::mem;
 use std::path::{Path, PathBuf};
-use syntect::parsing::SyntaxSet;
+use syntect::parsing::{SyntaxReference, SyntaxSet};
 
 /// An owned collection of buffers and associated path,
 /// representing a running editor environment.
@@ -15,21 +20,29 @@ pub struct Workspace {
     pub current_buffer: Option<Buffer>,
     current_buffer_index: Option<usize>,
     pub syntax_set: SyntaxSet,
+
+    /// Glob patterns (using EditorConfig syntax, matched against buffer
+    /// paths relative to the workspace) paired with the name of the syntax
+    /// to use for matching buffers, taking precedence over other detection
+    /// methods. The first matching pattern is used.
+    pub syntax_overrides: Vec<(String, String)>,
+    pub registers: Registers,
 }
 
 impl Workspace {
     /// Creates a new empty workspace for the specified path.
+    ///
+    /// If a syntax definitions folder is provided, its syntaxes are added to
+    /// the default set. The resulting set is cached in the user's cache
+    /// directory (`$XDG_CACHE_HOME/scribe` or `~/.cache/scribe`), and
+    /// re-used by subsequent workspaces until the folder's contents change.
     pub fn new(path: &Path, syntax_definitions: Option<&Path>) -> Result<Workspace> {
-        let mut syntax_set = SyntaxSet::load_defaults_newlines();
-
-        if let Some(path) = syntax_definitions {
-            // Add user syntaxes to the set.
-            let mut builder = syntax_set.into_builder();
-            builder.add_from_folder(path, true)?;
-
-            // Build the augmented syntax set.
-            syntax_set = builder.build();
-        }
+        let syntax_set = match syntax_definitions {
+            Some(definitions) => {
+                syntax_cache::load(definitions, syntax_cache::default_directory().as_deref())?
+            }
+            None => SyntaxSet::load_defaults_newlines(),
+        };
 
         Ok(Workspace {
             path: path.canonicalize()?,
@@ -38,6 +51,8 @@ impl Workspace {
             current_buffer: None,
             current_buffer_index: None,
             syntax_set,
+            syntax_overrides: Vec::new(),
+            registers: Registers::new(),
         })
     }
 
@@ -90,7 +105,9 @@ impl Workspace {
     /// The path is converted to its canonical, absolute equivalent;
     /// if a buffer with the specified path already exists,
     /// it is selected, rather than opening a duplicate buffer.
-    /// Any errors encountered while opening the buffer are returned.
+    /// EditorConfig properties for the path are applied to the buffer
+    /// (see `EditorConfig::resolve`) when they can be resolved, and any
+    /// errors encountered while opening the buffer are returned.
     ///
     /// # Examples
     ///
@@ -112,7 +129,10 @@ impl Workspace {
         if self.select_buffer_by_path(path) {
             Ok(())
         } else {
-            let buffer = Buffer::from_file(path)?;
+            let mut buffer = Buffer::from_file(path)?;
+            if let Ok(config) = EditorConfig::resolve(path, &self.path) {
+                config.apply(&mut buffer);
+            }
             self.add_buffer(buffer);
 
             Ok(())
@@ -332,13 +352,79 @@ impl Workspace {
             .current_buffer
             .as_ref()
             .ok_or(ErrorKind::EmptyWorkspace)?;
-        let data = buf.data();
-        let syntax_definition = buf
-            .syntax_definition
+        if buf.syntax_definition.is_none() {
+            bail!(ErrorKind::MissingSyntax);
+        }
+
+        Ok(buf.tokens(&self.syntax_set))
+    }
+
+    /// Returns the scope stack at the current buffer's cursor position.
+    /// See `Buffer::scope_at` for details on when `ErrorKind::MissingScope`
+    /// is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::{Buffer, Workspace};
+    /// use std::path::Path;
+    ///
+    /// let mut workspace = Workspace::new(Path::new("tests/sample"), None).unwrap();
+    /// assert!(workspace.current_buffer_scope().is_err());
+    ///
+    /// let mut buf = Buffer::new();
+    /// buf.insert("scribe");
+    /// workspace.add_buffer(buf);
+    /// assert_eq!(
+    ///     workspace.current_buffer_scope().unwrap(),
+    ///     "text.plain".parse().unwrap()
+    /// );
+    /// ```
+    pub fn current_buffer_scope(&self) -> Result<ScopeStack> {
+        let buf = self
+            .current_buffer
+            .as_ref()
+            .ok_or(ErrorKind::EmptyWorkspace)?;
+        if buf.syntax_definition.is_none() {
+            bail!(ErrorKind::MissingSyntax);
+        }
+
+        buf.scope_at(buf.cursor.position, &self.syntax_set)
+    }
+
+    /// Renders the current buffer (or a range of it) as a standalone HTML
+    /// document, using the workspace's syntax set. See `Buffer::to_html`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::{Buffer, Styler, Workspace};
+    /// use scribe::highlighting::HtmlOptions;
+    /// use std::path::Path;
+    ///
+    /// let mut workspace = Workspace::new(Path::new("tests/sample"), None).unwrap();
+    /// let styler = Styler::from_default_theme("base16-ocean.dark").unwrap();
+    /// let options = HtmlOptions::default();
+    /// assert!(workspace.current_buffer_html(&styler, None, &options).is_err());
+    ///
+    /// let mut buf = Buffer::new();
+    /// buf.insert("scribe");
+    /// workspace.add_buffer(buf);
+    /// let html = workspace.current_buffer_html(&styler, None, &options).unwrap();
+    /// assert!(html.contains(">scribe</span>"));
+    /// ```
+    pub fn current_buffer_html(
+        &self,
+        styler: &Styler,
+        range: Option<Range>,
+        options: &HtmlOptions,
+    ) -> Result<String> {
+        let buf = self
+            .current_buffer
             .as_ref()
-            .ok_or(ErrorKind::MissingSyntax)?;
+            .ok_or(ErrorKind::EmptyWorkspace)?;
 
-        Ok(TokenSet::new(data, syntax_definition, &self.syntax_set))
+        buf.to_html(&self.syntax_set, styler, range, options)
     }
 
     /// Returns path references to all buffers in the workspace.
@@ -394,6 +480,12 @@ impl Workspace {
     /// changed, this method can be used to attempt the assignment again, in
     /// hopes for a more accurate match.
     ///
+    /// Definitions are selected using, in order of precedence: the workspace's
+    /// `syntax_overrides`, vim/Emacs modelines in the buffer's content, its
+    /// file extension or name, and its first line (e.g. a shebang). Buffers
+    /// whose syntax was chosen using `set_current_syntax_by_name` (i.e. with
+    /// `syntax_locked` set) are left unchanged.
+    ///
     /// # Examples
     ///
     /// ```
@@ -427,23 +519,137 @@ impl Workspace {
     ///     "Rust"
     /// );
     ///
+    /// // Overrides take precedence over the file extension.
+    /// workspace.syntax_overrides.push(("*.rs".to_string(), "Plain Text".to_string()));
+    /// workspace.update_current_syntax();
+    ///
+    /// assert_eq!(
+    ///     workspace.current_buffer.as_ref().unwrap().syntax_definition.as_ref().unwrap().name,
+    ///     "Plain Text"
+    /// );
     /// ```
     pub fn update_current_syntax(&mut self) -> Result<()> {
+        let buffer = self
+            .current_buffer
+            .as_ref()
+            .ok_or(ErrorKind::EmptyWorkspace)?;
+        if buffer.syntax_locked && buffer.syntax_definition.is_some() {
+            return Ok(());
+        }
+        let definition = self.detect_syntax(buffer).clone();
+        if let Some(buffer) = self.current_buffer.as_mut() {
+            buffer.syntax_definition = Some(definition);
+        }
+
+        Ok(())
+    }
+
+    /// Sets the current buffer's syntax definition to the one with the
+    /// specified name (compared case-insensitively if there's no exact match),
+    /// locking it so that it isn't replaced by `update_current_syntax`.
+    /// Returns an `ErrorKind::UnknownSyntax` error if there's no such syntax.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::{Buffer, Workspace};
+    /// use std::path::{Path, PathBuf};
+    ///
+    /// let mut workspace = Workspace::new(Path::new("tests/sample"), None).unwrap();
+    /// let mut buf = Buffer::new();
+    /// buf.path = Some(PathBuf::from("mod.rs"));
+    /// workspace.add_buffer(buf);
+    ///
+    /// workspace.set_current_syntax_by_name("python").unwrap();
+    /// workspace.update_current_syntax().unwrap();
+    /// assert_eq!(
+    ///     workspace.current_buffer.as_ref().unwrap().syntax_definition.as_ref().unwrap().name,
+    ///     "Python"
+    /// );
+    /// assert!(workspace.set_current_syntax_by_name("scribe").is_err());
+    /// ```
+    pub fn set_current_syntax_by_name(&mut self, name: &str) -> Result<()> {
+        let definition = self
+            .syntax_set
+            .find_syntax_by_name(name)
+            .or_else(|| {
+                self.syntax_set
+                    .syntaxes()
+                    .iter()
+                    .find(|syntax| syntax.name.eq_ignore_ascii_case(name))
+            })
+            .ok_or_else(|| ErrorKind::UnknownSyntax(name.to_string()))?
+            .clone();
         let buffer = self
             .current_buffer
             .as_mut()
             .ok_or(ErrorKind::EmptyWorkspace)?;
-        let definition = buffer
-            .file_extension()
-            .or_else(|| buffer.file_name())
-            .and_then(|ex| self.syntax_set.find_syntax_by_extension(&ex))
-            .or_else(|| Some(self.syntax_set.find_syntax_plain_text()))
-            .cloned();
-        buffer.syntax_definition = definition;
+        buffer.syntax_definition = Some(definition);
+        buffer.syntax_locked = true;
 
         Ok(())
     }
 
+    /// The names of all syntaxes available to the workspace, sorted
+    /// alphabetically, for use with `set_current_syntax_by_name`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scribe::Workspace;
+    /// use std::path::Path;
+    ///
+    /// let workspace = Workspace::new(Path::new("tests/sample"), None).unwrap();
+    /// assert!(workspace.syntax_names().contains(&"Rust"));
+    /// ```
+    pub fn syntax_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .syntax_set
+            .syntaxes()
+            .iter()
+            .map(|syntax| syntax.name.as_str())
+            .collect();
+        names.sort_by_key(|name| name.to_lowercase());
+        names.dedup();
+
+        names
+    }
+
+    fn detect_syntax(&self, buffer: &Buffer) -> &SyntaxReference {
+        let data = buffer.data();
+
+        self.override_syntax(buffer)
+            .or_else(|| {
+                modeline::syntax_name(&data)
+                    .and_then(|name| self.syntax_set.find_syntax_by_token(&name))
+            })
+            .or_else(|| {
+                buffer
+                    .file_extension()
+                    .or_else(|| buffer.file_name())
+                    .and_then(|ex| self.syntax_set.find_syntax_by_extension(&ex))
+            })
+            .or_else(|| {
+                data.lines()
+                    .next()
+                    .and_then(|line| self.syntax_set.find_syntax_by_first_line(line))
+            })
+            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text())
+    }
+
+    // The syntax named by the first override whose pattern matches the
+    // buffer's path, if it exists in the workspace's syntax set.
+    fn override_syntax(&self, buffer: &Buffer) -> Option<&SyntaxReference> {
+        let path = buffer.path.as_deref()?;
+        let path = path.strip_prefix(&self.path).unwrap_or(path);
+        let path = path.to_string_lossy().replace('\\', "/");
+
+        self.syntax_overrides
+            .iter()
+            .find(|(pattern, _)| editorconfig::section_matches(pattern, &path))
+            .and_then(|(_, name)| self.syntax_set.find_syntax_by_name(name))
+    }
+
     fn select_buffer(&mut self, index: usize) -> bool {
         // Check-in current buffer, if it exists.
         if let Some(current_buffer) = self.current_buffer.as_mut() {
@@ -492,7 +698,8 @@ impl Workspace {
 #[cfg(test)]
 mod tests {
     use super::Workspace;
-    use crate::buffer::Buffer;
+    use crate::buffer::{Buffer, IndentStyle};
+    use crate::errors::*;
     use std::env;
     use std::path::{Path, PathBuf};
 
@@ -595,6 +802,20 @@ mod tests {
         assert_eq!(workspace.current_buffer.unwrap().data(), "it works!\n");
     }
 
+    #[test]
+    fn open_buffer_applies_editorconfig_properties() {
+        let mut workspace = Workspace::new(Path::new("tests/sample"), None).unwrap();
+        workspace
+            .open_buffer(Path::new("tests/sample/editorconfig/nested/file.rs"))
+            .unwrap();
+
+        let buffer = workspace.current_buffer.as_ref().unwrap();
+        assert_eq!(buffer.indentation.style, IndentStyle::Tabs);
+        assert_eq!(buffer.indentation.width, 8);
+        assert!(buffer.save_options.trim_trailing_whitespace);
+        assert!(buffer.save_options.insert_final_newline);
+    }
+
     #[test]
     fn open_buffer_does_not_open_a_buffer_already_in_the_workspace() {
         let mut workspace = Workspace::new(Path::new("tests/sample"), None).unwrap();
@@ -865,4 +1086,104 @@ mod tests {
             "Makefile"
         );
     }
+
+    // The name of the syntax detected for a buffer with the path and content.
+    fn detected_syntax(workspace: &mut Workspace, path: Option<&str>, content: &str) -> String {
+        let mut buf = Buffer::new();
+        buf.path = path.map(PathBuf::from);
+        buf.insert(content);
+        workspace.add_buffer(buf);
+
+        workspace
+            .current_buffer
+            .as_ref()
+            .unwrap()
+            .syntax_definition
+            .as_ref()
+            .unwrap()
+            .name
+            .clone()
+    }
+
+    #[test]
+    fn update_current_syntax_uses_the_first_line_and_modelines() {
+        let mut workspace = Workspace::new(Path::new("tests/sample"), None).unwrap();
+
+        assert_eq!(
+            detected_syntax(&mut workspace, Some("script"), "#!/usr/bin/env python3\n"),
+            "Python"
+        );
+        assert_eq!(
+            detected_syntax(&mut workspace, None, "<?xml version=\"1.0\"?>\n"),
+            "XML"
+        );
+
+        // Modelines take precedence over file extensions.
+        assert_eq!(
+            detected_syntax(&mut workspace, Some("config.txt"), "# vim: ft=ruby\n"),
+            "Ruby"
+        );
+        assert_eq!(
+            detected_syntax(&mut workspace, None, "// -*- mode: rust -*-\n"),
+            "Rust"
+        );
+    }
+
+    #[test]
+    fn update_current_syntax_prefers_matching_overrides() {
+        let mut workspace = Workspace::new(Path::new("tests/sample"), None).unwrap();
+        workspace.syntax_overrides = vec![
+            ("*.conf.j2".to_string(), "YAML".to_string()),
+            ("templates/*.rs".to_string(), "Unknown".to_string()),
+            ("templates/**".to_string(), "Python".to_string()),
+        ];
+
+        let path = workspace.path.join("app.conf.j2");
+        assert_eq!(
+            detected_syntax(&mut workspace, path.to_str(), "# vim: ft=ruby\n"),
+            "YAML"
+        );
+
+        // Overrides naming unknown syntaxes are skipped.
+        let path = workspace.path.join("templates/mod.rs");
+        assert_eq!(detected_syntax(&mut workspace, path.to_str(), ""), "Rust");
+        let path = workspace.path.join("templates/page.html");
+        assert_eq!(detected_syntax(&mut workspace, path.to_str(), ""), "Python");
+    }
+
+    #[test]
+    fn set_current_syntax_by_name_locks_the_syntax() {
+        let mut workspace = Workspace::new(Path::new("tests/sample"), None).unwrap();
+        assert_eq!(detected_syntax(&mut workspace, Some("mod.rs"), ""), "Rust");
+
+        workspace.set_current_syntax_by_name("Python").unwrap();
+        let buffer = workspace.current_buffer.as_mut().unwrap();
+        assert!(buffer.syntax_locked);
+        buffer.path = Some(PathBuf::from("Makefile"));
+        workspace.update_current_syntax().unwrap();
+        let buffer = workspace.current_buffer.as_mut().unwrap();
+        assert_eq!(buffer.syntax_definition.as_ref().unwrap().name, "Python");
+
+        // Unlocking the syntax resumes detection.
+        buffer.syntax_locked = false;
+        workspace.update_current_syntax().unwrap();
+        let buffer = workspace.current_buffer.as_ref().unwrap();
+        assert_eq!(buffer.syntax_definition.as_ref().unwrap().name, "Makefile");
+    }
+
+    #[test]
+    fn set_current_syntax_by_name_rejects_unknown_names() {
+        let mut workspace = Workspace::new(Path::new("tests/sample"), None).unwrap();
+        match workspace.set_current_syntax_by_name("Rust") {
+            Err(Error(ErrorKind::EmptyWorkspace, _)) => (),
+            _ => panic!("expected an empty workspace error"),
+        }
+
+        workspace.add_buffer(Buffer::new());
+        match workspace.set_current_syntax_by_name("scribe") {
+            Err(Error(ErrorKind::UnknownSyntax(name), _)) => assert_eq!(name, "scribe"),
+            _ => panic!("expected an unknown syntax error"),
+        }
+        assert!(!workspace.current_buffer.as_ref().unwrap().syntax_locked);
+    }
 }